@@ -0,0 +1,101 @@
+// particle data is only written, the benchmark measures spawning
+#![allow(dead_code)]
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use my_ecs::prelude::*;
+
+struct Position
+{
+    x: f32,
+    y: f32
+}
+
+impl Component for Position
+{
+    type Storage = VecStore<Self>;
+}
+
+struct Velocity
+{
+    x: f32,
+    y: f32
+}
+
+impl Component for Velocity
+{
+    type Storage = VecStore<Self>;
+}
+
+struct Lifetime(f32);
+
+impl Component for Lifetime
+{
+    type Storage = VecStore<Self>;
+}
+
+const PARTICLES: usize = 100_000;
+
+fn particle(i: usize) -> (Position, Velocity, Lifetime)
+{
+    let f = i as f32;
+    (Position{ x: f, y: f }, Velocity{ x: 1.0, y: -1.0 }, Lifetime(f * 0.01))
+}
+
+/// World with a few unrelated component stores, so every spawn has more than one store to grow
+fn setup() -> World
+{
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.register_component::<Velocity>();
+    world.register_component::<Lifetime>();
+    world
+}
+
+fn spawn_particles(c: &mut Criterion)
+{
+    let mut group = c.benchmark_group("spawn_100k_particles");
+    group.sample_size(10);
+
+    group.bench_function("entity_builder", |b|
+    {
+        b.iter_batched(setup, |mut world|
+        {
+            for i in 0..PARTICLES
+            {
+                let (position, velocity, lifetime) = particle(i);
+                world.create_entity()
+                    .with_component(position)
+                    .with_component(velocity)
+                    .with_component(lifetime)
+                    .build();
+            }
+            black_box(world)
+        }, BatchSize::LargeInput);
+    });
+
+    group.bench_function("spawn", |b|
+    {
+        b.iter_batched(setup, |mut world|
+        {
+            for i in 0..PARTICLES
+            {
+                world.spawn(particle(i));
+            }
+            black_box(world)
+        }, BatchSize::LargeInput);
+    });
+
+    group.bench_function("spawn_batch", |b|
+    {
+        b.iter_batched(setup, |mut world|
+        {
+            world.spawn_batch((0..PARTICLES).map(particle));
+            black_box(world)
+        }, BatchSize::LargeInput);
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, spawn_particles);
+criterion_main!(benches);
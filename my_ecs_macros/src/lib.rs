@@ -0,0 +1,310 @@
+//! Derive macros for my_ecs, re-exported by my_ecs behind the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, LitStr, Member};
+
+/// Implements `Component`.
+/// `#[component(storage = "vec")]` (the default) or `#[component(storage = "sparse")]` selects the store,
+/// `#[component(on_add = path, on_remove = path)]` sets hooks taking `(&mut Registry, usize)`.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_component(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implements `Resource`.
+#[proc_macro_derive(Resource)]
+pub fn derive_resource(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::my_ecs::Resource for #name #type_generics #where_clause {}
+    }.into()
+}
+
+/// Implements `Bundle` for a struct whose fields are components.
+/// Fields marked with `#[bundle]` are nested bundles.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_bundle(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implements `SystemParam` for a struct whose fields are all system params.
+/// The first lifetime parameter of the struct is used as the World lifetime.
+#[proc_macro_derive(SystemParam)]
+pub fn derive_system_param(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_system_param(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implements `Reflect` for a struct whose fields all implement `Reflect`.
+/// Fields marked with `#[reflect(skip)]` are left out, tuple struct fields are named "0", "1" and so on.
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_reflect(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_component(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut storage = quote! { ::my_ecs::VecStore<Self> };
+    let mut hooks = Vec::new();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("component"))
+    {
+        attr.parse_nested_meta(|meta|
+        {
+            if meta.path.is_ident("storage")
+            {
+                let kind: LitStr = meta.value()?.parse()?;
+                storage = match kind.value().as_str()
+                {
+                    "vec" => quote! { ::my_ecs::VecStore<Self> },
+                    "sparse" => quote! { ::my_ecs::SparseStore<Self> },
+                    other => return Err(syn::Error::new(kind.span(), format!("unknown storage kind `{other}`, expected \"vec\" or \"sparse\""))),
+                };
+                Ok(())
+            }
+            else if meta.path.is_ident("on_add") || meta.path.is_ident("on_remove")
+            {
+                let hook = meta.path.get_ident().cloned();
+                let path: syn::Path = meta.value()?.parse()?;
+                hooks.push(quote! {
+                    fn #hook(registry: &mut ::my_ecs::Registry, id: usize)
+                    {
+                        #path(registry, id)
+                    }
+                });
+                Ok(())
+            }
+            else
+            {
+                Err(meta.error("unknown component attribute, expected `storage`, `on_add` or `on_remove`"))
+            }
+        })?;
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::my_ecs::Component for #name #type_generics #where_clause
+        {
+            type Storage = #storage;
+
+            #(#hooks)*
+        }
+    })
+}
+
+/// Returns the fields of a struct, or an error for enums and unions
+fn struct_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<&'a Fields>
+{
+    match &input.data
+    {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(syn::Error::new(input.span(), format!("{derive} can only be derived for structs"))),
+    }
+}
+
+fn expand_bundle(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let fields = struct_fields(input, "Bundle")?;
+
+    let mut members = Vec::new();
+    let mut bundles = Vec::new();
+    let mut is_nested = Vec::new();
+    for (index, field) in fields.iter().enumerate()
+    {
+        members.push(match &field.ident
+        {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        });
+        let ty = &field.ty;
+        let nested = field.attrs.iter().any(|attr| attr.path().is_ident("bundle"));
+        // plain fields are components, treated as single element bundles
+        bundles.push(if nested { quote! { #ty } } else { quote! { (#ty,) } });
+        is_nested.push(nested);
+    }
+
+    let vars: Vec<_> = (0..members.len()).map(|index| format_ident!("field_{}", index)).collect();
+    let values: Vec<_> = members.iter().zip(&is_nested).map(|(member, nested)|
+        if *nested { quote! { self.#member } } else { quote! { (self.#member,) } }
+    ).collect();
+    let taken: Vec<_> = vars.iter().zip(&is_nested).map(|(var, nested)|
+        if *nested { quote! { #var? } } else { quote! { #var?.0 } }
+    ).collect();
+
+    Ok(quote! {
+        impl #impl_generics ::my_ecs::Bundle for #name #type_generics #where_clause
+        {
+            fn type_ids() -> ::std::vec::Vec<::std::any::TypeId>
+            {
+                let mut type_ids = ::std::vec::Vec::new();
+                #(type_ids.extend(<#bundles as ::my_ecs::Bundle>::type_ids());)*
+                type_ids
+            }
+
+            fn register_components(registry: &mut ::my_ecs::Registry)
+            {
+                #(<#bundles as ::my_ecs::Bundle>::register_components(registry);)*
+            }
+
+            fn insert_components(self, registry: &mut ::my_ecs::Registry, id: usize)
+            {
+                #(::my_ecs::Bundle::insert_components(#values, registry, id);)*
+            }
+
+            fn take_components(registry: &mut ::my_ecs::Registry, id: usize) -> ::std::option::Option<Self>
+            {
+                // take every field before bailing out so none are left behind
+                #(let #vars = <#bundles as ::my_ecs::Bundle>::take_components(registry, id);)*
+                ::std::option::Option::Some(Self { #(#members: #taken),* })
+            }
+        }
+    })
+}
+
+fn expand_reflect(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let fields = struct_fields(input, "Reflect")?;
+
+    let mut members = Vec::new();
+    let mut names = Vec::new();
+    for (index, field) in fields.iter().enumerate()
+    {
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("reflect"))
+        {
+            attr.parse_nested_meta(|meta|
+            {
+                if meta.path.is_ident("skip")
+                {
+                    skip = true;
+                    Ok(())
+                }
+                else
+                {
+                    Err(meta.error("unknown reflect attribute, expected `skip`"))
+                }
+            })?;
+        }
+        if skip
+        {
+            continue;
+        }
+
+        let (member, field_name) = match &field.ident
+        {
+            Some(ident) => (Member::Named(ident.clone()), ident.to_string()),
+            None => (Member::Unnamed(index.into()), index.to_string()),
+        };
+        members.push(member);
+        names.push(field_name);
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::my_ecs::Reflect for #name #type_generics #where_clause
+        {
+            fn as_any(&self) -> &dyn ::std::any::Any
+            {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::std::any::Any
+            {
+                self
+            }
+
+            fn field_names(&self) -> &'static [&'static str]
+            {
+                &[#(#names),*]
+            }
+
+            fn field(&self, name: &str) -> ::std::option::Option<&dyn ::my_ecs::Reflect>
+            {
+                match name
+                {
+                    #(#names => ::std::option::Option::Some(&self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> ::std::option::Option<&mut dyn ::my_ecs::Reflect>
+            {
+                match name
+                {
+                    #(#names => ::std::option::Option::Some(&mut self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+fn expand_system_param(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+    let name = &input.ident;
+    let fields = struct_fields(input, "SystemParam")?;
+    let (_, type_generics, where_clause) = input.generics.split_for_impl();
+
+    // reuse the struct's own lifetime as the World lifetime, or introduce one
+    let mut generics = input.generics.clone();
+    let world_lifetime = match input.generics.lifetimes().next()
+    {
+        Some(param) => param.lifetime.clone(),
+        None =>
+        {
+            let lifetime: syn::Lifetime = syn::parse_quote!('__w);
+            generics.params.insert(0, syn::parse_quote!(#lifetime));
+            lifetime
+        }
+    };
+    let (impl_generics, _, _) = generics.split_for_impl();
+
+    let members: Vec<Member> = fields.iter().enumerate().map(|(index, field)|
+        match &field.ident
+        {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        }
+    ).collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    Ok(quote! {
+        impl #impl_generics ::my_ecs::SystemParam<#world_lifetime> for #name #type_generics #where_clause
+        {
+            fn fetch(world: &#world_lifetime ::my_ecs::World) -> ::std::result::Result<Self, ::my_ecs::ResourceError>
+            {
+                ::std::result::Result::Ok(Self {
+                    #(#members: <#types as ::my_ecs::SystemParam<#world_lifetime>>::fetch(world)?,)*
+                })
+            }
+
+            fn is_send() -> bool
+            {
+                true #(&& <#types as ::my_ecs::SystemParam<#world_lifetime>>::is_send())*
+            }
+
+            fn validate_thread(world: &::my_ecs::World) -> ::std::result::Result<(), ::my_ecs::ResourceError>
+            {
+                #(<#types as ::my_ecs::SystemParam<#world_lifetime>>::validate_thread(world)?;)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
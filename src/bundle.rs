@@ -0,0 +1,100 @@
+use std::any::TypeId;
+
+use crate::{component::Component, registry::Registry};
+
+/// A group of components that are inserted into or removed from an entity together.
+/// Implemented for tuples of up to 16 components, and can be implemented for structs
+/// by delegating to each field.
+pub trait Bundle: Send + Sync + Sized + 'static
+{
+    /// Returns the TypeIds of every component in the bundle
+    fn type_ids() -> Vec<TypeId>;
+
+    /// Registers every component in the bundle that isn't registered yet
+    fn register_components(registry: &mut Registry);
+
+    /// Writes every component in the bundle into the stores at the entity id
+    fn insert_components(self, registry: &mut Registry, id: usize);
+
+    /// Takes every component in the bundle out of the stores at the entity id.
+    /// Called by Registry::remove_bundle once every component is known to exist, returns None if any was missing.
+    fn take_components(registry: &mut Registry, id: usize) -> Option<Self>;
+}
+
+/// The empty bundle, for spawning or inserting nothing
+impl Bundle for ()
+{
+    fn type_ids() -> Vec<TypeId>
+    {
+        Vec::new()
+    }
+
+    fn register_components(_registry: &mut Registry) {}
+
+    fn insert_components(self, _registry: &mut Registry, _id: usize) {}
+
+    fn take_components(_registry: &mut Registry, _id: usize) -> Option<Self>
+    {
+        Some(())
+    }
+}
+
+macro_rules! impl_bundle_for_tuple
+{
+    ($($name: ident),*) =>
+    {
+        impl<$($name: Component),*> Bundle for ($($name,)*)
+        {
+            fn type_ids() -> Vec<TypeId>
+            {
+                vec![$(TypeId::of::<$name>()),*]
+            }
+
+            fn register_components(registry: &mut Registry)
+            {
+                $(
+                    if !registry.components.contains_key(&TypeId::of::<$name>())
+                    {
+                        registry.register_component::<$name>();
+                    }
+                )*
+            }
+
+            #[allow(non_snake_case)]
+            fn insert_components(self, registry: &mut Registry, id: usize)
+            {
+                let ($($name,)*) = self;
+                $(registry.set_component(id, $name);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn take_components(registry: &mut Registry, id: usize) -> Option<Self>
+            {
+                // check every component first so a partial match removes nothing
+                if !($(registry.has_component::<$name>(id))&&*)
+                {
+                    return None;
+                }
+                let ($($name,)*) = ($(registry.take_component::<$name>(id),)*);
+                Some(($($name?,)*))
+            }
+        }
+    }
+}
+
+impl_bundle_for_tuple!(A);
+impl_bundle_for_tuple!(A, B);
+impl_bundle_for_tuple!(A, B, C);
+impl_bundle_for_tuple!(A, B, C, D);
+impl_bundle_for_tuple!(A, B, C, D, E);
+impl_bundle_for_tuple!(A, B, C, D, E, F);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_bundle_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
@@ -0,0 +1,278 @@
+use std::{fmt, sync::Mutex};
+
+use crate::{bundle::Bundle, relation::Relation, resource::ResourceError, system::SystemParam, world::World};
+
+/// A deferred change to the World, applied when the World is flushed
+pub type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// A queued command that failed when it was applied, collected by World::take_command_errors
+pub struct CommandError
+{
+    pub(crate) message: String
+}
+
+impl CommandError
+{
+    pub fn new(message: impl Into<String>) -> Self
+    {
+        Self { message: message.into() }
+    }
+}
+
+impl fmt::Display for CommandError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for CommandError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Commands recorded through a shared reference to the World, safe to push to from many threads
+pub struct CommandQueue
+{
+    commands: Mutex<Vec<Command>>,
+    errors: Vec<CommandError> // failures of applied commands, until taken
+}
+
+impl CommandQueue
+{
+    pub fn new() -> Self
+    {
+        Self { commands: Mutex::new(Vec::new()), errors: Vec::new() }
+    }
+
+    /// Adds a command to the end of the queue
+    pub fn push(&self, command: Command)
+    {
+        // a poisoned queue still holds valid commands
+        self.commands.lock().unwrap_or_else(|err| err.into_inner()).push(command);
+    }
+
+    /// Removes and returns every queued command in order
+    pub fn take(&mut self) -> Vec<Command>
+    {
+        std::mem::take(self.commands.get_mut().unwrap_or_else(|err| err.into_inner()))
+    }
+
+    pub fn is_empty(&mut self) -> bool
+    {
+        self.commands.get_mut().unwrap_or_else(|err| err.into_inner()).is_empty()
+    }
+
+    /// Records that an applied command failed
+    pub fn report(&mut self, error: CommandError)
+    {
+        self.errors.push(error);
+    }
+
+    /// Removes and returns the failures of every command applied since the last call, in order
+    pub fn take_errors(&mut self) -> Vec<CommandError>
+    {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+impl Default for CommandQueue
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// Records changes to the World through a shared reference, applied by World::flush
+pub struct Commands<'w>
+{
+    world: &'w World
+}
+
+impl<'w> Commands<'w>
+{
+    pub fn new(world: &'w World) -> Self
+    {
+        Self { world }
+    }
+
+    /// Reserves an entity id, the entity exists once commands are flushed
+    pub fn reserve_entity(&self) -> usize
+    {
+        self.world.reserve_entity()
+    }
+
+    /// Reserves an entity id and queues adding the bundle to it, returns the id
+    pub fn spawn<B: Bundle>(&self, bundle: B) -> usize
+    {
+        let id = self.reserve_entity();
+        self.insert_bundle(id, bundle);
+        id
+    }
+
+    /// Queues adding every component in the bundle to an entity
+    pub fn insert_bundle<B: Bundle>(&self, id: usize, bundle: B)
+    {
+        self.add(move | world: &mut World | world.insert_bundle(id, bundle));
+    }
+
+    /// Queues removing every component in the bundle from an entity
+    pub fn remove_bundle<B: Bundle>(&self, id: usize)
+    {
+        self.add(move | world: &mut World | { world.remove_bundle::<B>(id); });
+    }
+
+    /// Queues despawning an entity
+    pub fn despawn(&self, id: usize)
+    {
+        self.add(move | world: &mut World | { world.despawn(id); });
+    }
+
+    /// Queues making parent the parent of child.
+    /// If that would create a cycle nothing changes and the error is reported through World::take_command_errors.
+    pub fn set_parent(&self, child: usize, parent: usize)
+    {
+        self.add(move | world: &mut World |
+        {
+            if let Err(err) = world.set_parent(child, parent)
+            {
+                world.report_command_error(CommandError::new(format!("Failed to apply set_parent command: {err}")));
+            }
+        });
+    }
+
+    /// Queues adding child to the end of parent's children, reported like set_parent if it would create a cycle
+    pub fn add_child(&self, parent: usize, child: usize)
+    {
+        self.set_parent(child, parent);
+    }
+
+    /// Queues detaching an entity from its parent
+    pub fn remove_parent(&self, child: usize)
+    {
+        self.add(move | world: &mut World | { world.remove_parent(child); });
+    }
+
+    /// Queues despawning an entity and all of its descendants
+    pub fn despawn_recursive(&self, id: usize)
+    {
+        self.add(move | world: &mut World | { world.despawn_recursive(id); });
+    }
+
+    /// Queues linking source to target through the relation, inactive entities are reported through World::take_command_errors
+    pub fn add_relation<R: Relation>(&self, source: usize, relation: R, target: usize)
+    {
+        self.add(move | world: &mut World |
+        {
+            if let Err(err) = world.add_relation(source, relation, target)
+            {
+                world.report_command_error(CommandError::new(format!("Failed to apply add_relation command: {err}")));
+            }
+        });
+    }
+
+    /// Queues removing the relation of type R from source
+    pub fn remove_relation<R: Relation>(&self, source: usize)
+    {
+        self.add(move | world: &mut World | { world.remove_relation::<R>(source); });
+    }
+
+    /// Queues a custom command
+    pub fn add(&self, command: impl FnOnce(&mut World) + Send + 'static)
+    {
+        self.world.queue_command(Box::new(command));
+    }
+}
+
+impl<'w> SystemParam<'w> for Commands<'w>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        Ok(Commands::new(world))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::thread;
+
+    use crate::component_store::VecStore;
+    use crate::component::Component;
+
+    use super::*;
+
+    #[test]
+    fn test_spawn_from_shared_world()
+    {
+        let mut world = World::new();
+        let target = world.spawn((Projectile{ target: None },));
+        world.despawn(target);
+
+        // reserve from several threads through &World
+        let ids: Vec<usize> = thread::scope(|s|
+        {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(||
+            {
+                let commands = world.commands();
+                let target = commands.reserve_entity();
+                commands.spawn((Projectile{ target: Some(target) },))
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        // the projectiles don't exist until the commands are flushed
+        assert!(world.query().with_component::<Projectile>().get().is_empty());
+        world.flush();
+
+        let mut projectiles = world.query().with_component::<Projectile>().get();
+        projectiles.sort();
+        let mut expected = ids.clone();
+        expected.sort();
+        assert_eq!(projectiles, expected);
+
+        // the dropped id was reused, every reserved id refers to an active entity
+        let store = world.get_components::<Projectile>().unwrap();
+        assert_eq!(store.len(), 8);
+        for id in ids
+        {
+            let target = store.get(id).unwrap().as_ref().unwrap().target.unwrap();
+            assert!(world.get_entity(target).unwrap().is_active);
+        }
+    }
+
+    #[test]
+    fn test_command_errors()
+    {
+        let mut world = World::new();
+        let parent = world.spawn(());
+        let child = world.spawn(());
+
+        let commands = world.commands();
+        commands.add_child(parent, child);
+        // would make the child its own grandparent
+        commands.set_parent(parent, child);
+        world.flush();
+
+        let errors = world.take_command_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(world.parent(child), Some(parent));
+        assert_eq!(world.parent(parent), None);
+        assert!(world.take_command_errors().is_empty());
+    }
+
+    struct Projectile
+    {
+        pub target: Option<usize>
+    }
+
+    impl Component for Projectile
+    {
+        type Storage = VecStore<Self>;
+    }
+}
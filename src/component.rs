@@ -0,0 +1,96 @@
+use std::{alloc::Layout, any::{self, Any, TypeId}};
+
+use crate::{component_store::TypedStore, dynamic::ComponentDescriptor, registry::Registry};
+
+/// A type that can be attached to entities as a component.
+/// Usually implemented with `#[derive(Component)]`, `#[component(storage = "sparse")]` selects a SparseStore
+/// and `#[component(on_add = path, on_remove = path)]` sets the hooks.
+pub trait Component: Any + Send + Sync + Sized
+{
+    /// Store the components are kept in, VecStore or SparseStore
+    type Storage: TypedStore<Self>;
+
+    /// Called once when the component type is registered
+    fn on_register(_registry: &mut Registry) {}
+
+    /// Called after the component is added to an entity
+    fn on_add(_registry: &mut Registry, _id: usize) {}
+
+    /// Called before the component is removed from an entity, while it is still stored
+    fn on_remove(_registry: &mut Registry, _id: usize) {}
+}
+
+/// Identifies a registered component type, either a Rust type or one defined at runtime with a ComponentDescriptor
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct ComponentId(pub(crate) usize);
+
+impl ComponentId
+{
+    /// Returns the index of the component type, in the order component types were registered
+    pub fn index(&self) -> usize
+    {
+        self.0
+    }
+}
+
+/// Name and memory layout of a registered component type, see Registry::component_info
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ComponentInfo
+{
+    id: ComponentId,
+    name: String,
+    type_id: Option<TypeId>, // None for components defined at runtime
+    layout: Layout,
+    reflected: bool // registered with register_reflect
+}
+
+impl ComponentInfo
+{
+    pub(crate) fn of<T: Component>(id: ComponentId, reflected: bool) -> Self
+    {
+        Self { id, name: any::type_name::<T>().to_owned(), type_id: Some(TypeId::of::<T>()), layout: Layout::new::<T>(), reflected }
+    }
+
+    pub(crate) fn dynamic(id: ComponentId, descriptor: &ComponentDescriptor) -> Self
+    {
+        Self { id, name: descriptor.name().to_owned(), type_id: None, layout: descriptor.layout(), reflected: false }
+    }
+
+    pub fn id(&self) -> ComponentId
+    {
+        self.id
+    }
+
+    /// Returns the full type name, such as `my_ecs::name::Name`, or the descriptor name of a runtime defined component
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    /// Returns the TypeId of the Rust type, None for components defined at runtime
+    pub fn type_id(&self) -> Option<TypeId>
+    {
+        self.type_id
+    }
+
+    pub fn layout(&self) -> Layout
+    {
+        self.layout
+    }
+
+    pub fn size(&self) -> usize
+    {
+        self.layout.size()
+    }
+
+    pub fn align(&self) -> usize
+    {
+        self.layout.align()
+    }
+
+    /// Returns true if the component can be accessed through Reflect
+    pub fn is_reflected(&self) -> bool
+    {
+        self.reflected
+    }
+}
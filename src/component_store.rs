@@ -1,4 +1,4 @@
-use std::{any::Any, fmt, error};
+use std::{any::{self, Any}, collections::HashMap, fmt, error, ops::{Deref, DerefMut}};
 pub use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 // use anyhow;
@@ -6,7 +6,7 @@ pub use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 pub struct ComponentError
 {
     // code: usize,
-    message: String
+    pub(crate) message: String
 }
 
 impl fmt::Display for ComponentError
@@ -34,6 +34,29 @@ impl<T: error::Error + Send + Sync + 'static> From<T> for ComponentError
 }
 
 
+/// Ticks a component was added and last mutably accessed at
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ComponentTicks
+{
+    pub added: u64,
+    pub changed: u64
+}
+
+impl ComponentTicks
+{
+    /// Returns true if the component was added after last_change_tick
+    pub fn is_added(&self, last_change_tick: u64) -> bool
+    {
+        self.added > last_change_tick
+    }
+
+    /// Returns true if the component was added or mutably accessed after last_change_tick
+    pub fn is_changed(&self, last_change_tick: u64) -> bool
+    {
+        self.changed > last_change_tick
+    }
+}
+
 pub trait ComponentStore: Send + Sync
 {
     fn push_none(&mut self);
@@ -43,45 +66,126 @@ pub trait ComponentStore: Send + Sync
     fn resize_to_nones(&mut self, len: usize);
 
     fn drop(&mut self, index: usize);
+
+    /// Sets the tick added and mutably accessed components are marked with
+    fn set_change_tick(&mut self, tick: u64);
+
+    /// Returns the change ticks of the component at index, None if there is none
+    fn ticks(&self, index: usize) -> Option<ComponentTicks>;
+
+    /// Takes the component at index out of the store with its type erased, None if there is none
+    fn take_boxed(&mut self, index: usize) -> Option<Box<dyn Any + Send + Sync>>;
+
+    /// Returns a pointer to the component at index, None if there is none.
+    /// The pointer is valid until the store is next mutated.
+    fn get_ptr(&self, index: usize) -> Option<*const u8>;
+
+    /// Returns a mutable pointer to the component at index, marking it changed at the current change tick
+    fn get_mut_ptr(&mut self, index: usize) -> Option<*mut u8>;
     
     fn as_any(&self) -> &dyn Any;
 
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
+/// Write access to a component slot, with change detection.
+/// Mutably dereferencing it marks the component as changed.
+pub struct ComponentMut<'a, T>
+{
+    guard: RwLockWriteGuard<'a, Option<T>>,
+    changed: &'a mut u64,
+    change_tick: u64
+}
+
+impl<'a, T> Deref for ComponentMut<'a, T>
+{
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for ComponentMut<'a, T>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        *self.changed = self.change_tick;
+        &mut self.guard
+    }
+}
+
+/// Typed access to a store of components of type T
+pub trait TypedStore<T>: ComponentStore + Default + sealed::StoreWrite<T>
+{
+    fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<T>>, ComponentError>;
+
+    /// Returns the component at index, which is marked changed at the current change tick once mutably dereferenced
+    fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, T>, ComponentError>;
+
+    /// Stores the component at index, marked added at the current change tick, or changed if it replaces one
+    fn insert(&mut self, index: usize, value: T);
+
+    /// Marks the component at index as changed at the current change tick
+    fn mark_changed(&mut self, index: usize);
+}
+
+pub(crate) mod sealed
+{
+    use super::{ComponentError, RwLockWriteGuard};
+
+    /// Writing through a shared reference skips change detection, so only queries inside the crate may do it.
+    /// Other crates can't name this trait, so they can neither call write nor implement TypedStore.
+    pub trait StoreWrite<T>
+    {
+        /// Returns the component at index for writing through a shared reference, without marking it changed
+        fn write(&self, index: usize) -> Result<RwLockWriteGuard<'_, Option<T>>, ComponentError>;
+    }
+}
+
+/// Dense storage, one slot per entity.
+/// Fast to iterate, best for components most entities have.
 pub struct VecStore<T>
 {
-    data: Vec<RwLock<Option<T>>>
+    data: Vec<RwLock<Option<T>>>,
+    ticks: Vec<ComponentTicks>,
+    change_tick: u64
 }
 
 impl <T> VecStore<T>
 {
     pub fn new() -> Self
     {
-        Self { data: Vec::new() }
+        Self { data: Vec::new(), ticks: Vec::new(), change_tick: 0 }
     }
 
-    pub fn get(&self, index: usize) -> Result<RwLockReadGuard<Option<T>>, ComponentError>
+    pub fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<T>>, ComponentError>
     {
-        match self.data[index].read()
+        match self.slot(index, "get")?.read()
         {
             Ok(data) => Ok(data),
             Err(err) => 
             {
-                let comp_error = ComponentError { message: format!("Failed to get component at entity id {index}: {err:?}") };
+                let comp_error = ComponentError { message: format!("Failed to get component {} of entity {index}: {err:?}", any::type_name::<T>()) };
                 Err(comp_error)
             }
         }
     }
 
-    pub fn get_mut(&mut self, index: usize) -> Result<RwLockWriteGuard<Option<T>>, ComponentError>
+    /// Returns the component at index, which is marked changed at the current change tick once mutably dereferenced
+    pub fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, T>, ComponentError>
     {
-        match self.data[index].write()
+        let (Some(slot), Some(ticks)) = (self.data.get(index), self.ticks.get_mut(index)) else
         {
-            Ok(data) => Ok(data),
+            return Err(ComponentError { message: format!("Failed to get mut component {} of entity {index}: no such entity", any::type_name::<T>()) });
+        };
+        match slot.write()
+        {
+            Ok(guard) => Ok(ComponentMut { guard, changed: &mut ticks.changed, change_tick: self.change_tick }),
             Err(err) => 
             {
-                let comp_error = ComponentError { message: format!("Failed to get mut component at entity id {index}: {err:?}") };
+                let comp_error = ComponentError { message: format!("Failed to get mut component {} of entity {index}: {err:?}", any::type_name::<T>()) };
                 Err(comp_error)
             }
         }
@@ -92,6 +196,25 @@ impl <T> VecStore<T>
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool
+    {
+        self.data.is_empty()
+    }
+
+    /// Returns the slot of index, or an error naming what failed if index is past the end of the store
+    fn slot(&self, index: usize, action: &str) -> Result<&RwLock<Option<T>>, ComponentError>
+    {
+        self.data.get(index)
+            .ok_or_else(|| ComponentError { message: format!("Failed to {action} component {} of entity {index}: no such entity", any::type_name::<T>()) })
+    }
+}
+
+impl<T> Default for VecStore<T>
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
 }
 
 impl<T: Send + Sync + 'static> ComponentStore for VecStore<T>
@@ -99,23 +222,57 @@ impl<T: Send + Sync + 'static> ComponentStore for VecStore<T>
     fn push_none(&mut self)
     {
         self.data.push(RwLock::new(None));
+        self.ticks.push(ComponentTicks::default());
     }
 
     fn set_none(&mut self, index: usize) 
     {
-        let mut val = self.data[index].write().unwrap();
-        *val = None;
+        ComponentStore::drop(self, index);
     }
     
     fn resize_to_nones(&mut self, len: usize)
     {
         self.data.resize_with(len, || { RwLock::new(None) } );
+        self.ticks.resize(len, ComponentTicks::default());
     }
 
     fn drop(&mut self, index: usize)
     {
-        let mut val = self.data[index].write().unwrap();
-        *val = None;
+        if let Some(slot) = self.data.get_mut(index)
+        {
+            *slot.get_mut().unwrap_or_else(|err| err.into_inner()) = None;
+            self.ticks[index] = ComponentTicks::default();
+        }
+    }
+
+    fn set_change_tick(&mut self, tick: u64)
+    {
+        self.change_tick = tick;
+    }
+
+    fn ticks(&self, index: usize) -> Option<ComponentTicks>
+    {
+        self.ticks.get(index).copied()
+    }
+
+    fn take_boxed(&mut self, index: usize) -> Option<Box<dyn Any + Send + Sync>>
+    {
+        let component = self.data.get_mut(index)?.get_mut().ok()?.take()?;
+        self.ticks[index] = ComponentTicks::default();
+        Some(Box::new(component))
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<*const u8>
+    {
+        let component = self.data.get(index)?.read().ok()?;
+        component.as_ref().map(|component| component as *const T as *const u8)
+    }
+
+    fn get_mut_ptr(&mut self, index: usize) -> Option<*mut u8>
+    {
+        let component = self.data.get_mut(index)?.get_mut().ok()?.as_mut()? as *mut T as *mut u8;
+        self.ticks[index].changed = self.change_tick;
+        Some(component)
     }
 
     fn as_any(&self) -> &dyn Any
@@ -128,4 +285,217 @@ impl<T: Send + Sync + 'static> ComponentStore for VecStore<T>
         self as &mut dyn std::any::Any
     }
 
-}
\ No newline at end of file
+}
+impl<T: Send + Sync + 'static> TypedStore<T> for VecStore<T>
+{
+    fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<T>>, ComponentError>
+    {
+        VecStore::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, T>, ComponentError>
+    {
+        VecStore::get_mut(self, index)
+    }
+
+    fn insert(&mut self, index: usize, value: T)
+    {
+        if index >= self.data.len()
+        {
+            self.resize_to_nones(index + 1);
+        }
+
+        let slot = self.data[index].get_mut().unwrap_or_else(|err| err.into_inner());
+        if slot.replace(value).is_none()
+        {
+            self.ticks[index].added = self.change_tick;
+        }
+        self.ticks[index].changed = self.change_tick;
+    }
+
+    fn mark_changed(&mut self, index: usize)
+    {
+        if let Some(ticks) = self.ticks.get_mut(index)
+        {
+            ticks.changed = self.change_tick;
+        }
+    }
+}
+
+impl<T> sealed::StoreWrite<T> for VecStore<T>
+{
+    fn write(&self, index: usize) -> Result<RwLockWriteGuard<'_, Option<T>>, ComponentError>
+    {
+        self.slot(index, "write")?.write().map_err(|err| ComponentError { message: format!("Failed to write component {} of entity {index}: {err:?}", any::type_name::<T>()) })
+    }
+}
+
+/// Sparse storage, only entities with the component take up space.
+/// Cheap to add and remove, best for rare or short lived components.
+pub struct SparseStore<T>
+{
+    data: HashMap<usize, RwLock<Option<T>>>,
+    ticks: HashMap<usize, ComponentTicks>,
+    empty: RwLock<Option<T>>, // Always None, read for entities without the component
+    change_tick: u64
+}
+
+impl<T> SparseStore<T>
+{
+    pub fn new() -> Self
+    {
+        Self { data: HashMap::new(), ticks: HashMap::new(), empty: RwLock::new(None), change_tick: 0 }
+    }
+
+    pub fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<T>>, ComponentError>
+    {
+        match self.data.get(&index).unwrap_or(&self.empty).read()
+        {
+            Ok(data) => Ok(data),
+            Err(err) => 
+            {
+                let comp_error = ComponentError { message: format!("Failed to get component {} of entity {index}: {err:?}", any::type_name::<T>()) };
+                Err(comp_error)
+            }
+        }
+    }
+
+    /// Returns the component at index, which is marked changed at the current change tick once mutably dereferenced.
+    /// Fails if the entity has no component, use TypedStore::insert to add one.
+    pub fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, T>, ComponentError>
+    {
+        let slot = self.data.get(&index)
+            .ok_or_else(|| ComponentError { message: format!("Failed to get mut component {} of entity {index}: no component stored", any::type_name::<T>()) })?;
+        match slot.write()
+        {
+            Ok(guard) => Ok(ComponentMut { guard, changed: &mut self.ticks.entry(index).or_default().changed, change_tick: self.change_tick }),
+            Err(err) => 
+            {
+                let comp_error = ComponentError { message: format!("Failed to get mut component {} of entity {index}: {err:?}", any::type_name::<T>()) };
+                Err(comp_error)
+            }
+        }
+    }
+
+    /// Returns the number of entities with a stored slot
+    pub fn len(&self) -> usize
+    {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.data.is_empty()
+    }
+}
+
+impl<T> Default for SparseStore<T>
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl<T: Send + Sync + 'static> ComponentStore for SparseStore<T>
+{
+    fn push_none(&mut self) {}
+
+    fn set_none(&mut self, index: usize) 
+    {
+        self.data.remove(&index);
+        self.ticks.remove(&index);
+    }
+    
+    fn resize_to_nones(&mut self, len: usize)
+    {
+        self.data.retain(|index, _| *index < len);
+        self.ticks.retain(|index, _| *index < len);
+    }
+
+    fn drop(&mut self, index: usize)
+    {
+        self.data.remove(&index);
+        self.ticks.remove(&index);
+    }
+
+    fn set_change_tick(&mut self, tick: u64)
+    {
+        self.change_tick = tick;
+    }
+
+    fn ticks(&self, index: usize) -> Option<ComponentTicks>
+    {
+        self.ticks.get(&index).copied()
+    }
+
+    fn take_boxed(&mut self, index: usize) -> Option<Box<dyn Any + Send + Sync>>
+    {
+        self.ticks.remove(&index);
+        let component = self.data.remove(&index)?.into_inner().ok()??;
+        Some(Box::new(component))
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<*const u8>
+    {
+        let component = self.data.get(&index)?.read().ok()?;
+        component.as_ref().map(|component| component as *const T as *const u8)
+    }
+
+    fn get_mut_ptr(&mut self, index: usize) -> Option<*mut u8>
+    {
+        let component = self.data.get_mut(&index)?.get_mut().ok()?.as_mut()? as *mut T as *mut u8;
+        self.ticks.entry(index).or_default().changed = self.change_tick;
+        Some(component)
+    }
+
+    fn as_any(&self) -> &dyn Any
+    {
+        self as &dyn std::any::Any
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    {
+        self as &mut dyn std::any::Any
+    }
+}
+
+impl<T: Send + Sync + 'static> TypedStore<T> for SparseStore<T>
+{
+    fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<T>>, ComponentError>
+    {
+        SparseStore::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, T>, ComponentError>
+    {
+        SparseStore::get_mut(self, index)
+    }
+
+    fn insert(&mut self, index: usize, value: T)
+    {
+        let slot = self.data.entry(index).or_insert_with(|| RwLock::new(None)).get_mut().unwrap_or_else(|err| err.into_inner());
+        let ticks = self.ticks.entry(index).or_default();
+        if slot.replace(value).is_none()
+        {
+            ticks.added = self.change_tick;
+        }
+        ticks.changed = self.change_tick;
+    }
+
+    fn mark_changed(&mut self, index: usize)
+    {
+        self.ticks.entry(index).or_default().changed = self.change_tick;
+    }
+}
+
+impl<T> sealed::StoreWrite<T> for SparseStore<T>
+{
+    fn write(&self, index: usize) -> Result<RwLockWriteGuard<'_, Option<T>>, ComponentError>
+    {
+        // the shared empty slot must never be written to
+        let slot = self.data.get(&index)
+            .ok_or_else(|| ComponentError { message: format!("Failed to write component {} of entity {index}: no component stored", any::type_name::<T>()) })?;
+        slot.write().map_err(|err| ComponentError { message: format!("Failed to write component {} of entity {index}: {err:?}", any::type_name::<T>()) })
+    }
+}
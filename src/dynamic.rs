@@ -0,0 +1,353 @@
+use std::{
+    alloc::{self, Layout},
+    any::Any,
+    collections::HashMap,
+    ptr::{self, NonNull}
+};
+
+use crate::component_store::{ComponentStore, ComponentTicks};
+
+/// Describes a component type defined at runtime, such as by a mod, that has no Rust type behind it.
+/// Registered with Registry::register_dynamic_component, which returns the ComponentId to access it by.
+#[derive(Clone, Debug)]
+pub struct ComponentDescriptor
+{
+    name: String,
+    layout: Layout,
+    drop: Option<unsafe fn(*mut u8)>
+}
+
+impl ComponentDescriptor
+{
+    /// drop is called with a pointer to a component before its memory is freed, None if it needs no cleanup.
+    ///
+    /// # Safety
+    /// The bytes stored for the component must be safe to send to and share between threads,
+    /// stores of runtime defined components are Send and Sync regardless of what they hold.
+    /// drop must be safe to call on any valid component of the layout.
+    pub unsafe fn new(name: impl Into<String>, layout: Layout, drop: Option<unsafe fn(*mut u8)>) -> Self
+    {
+        Self { name: name.into(), layout, drop }
+    }
+
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    pub fn layout(&self) -> Layout
+    {
+        self.layout
+    }
+
+    /// Returns true if other has the same name, layout and drop fn, so components of both can share a store
+    pub(crate) fn matches(&self, other: &ComponentDescriptor) -> bool
+    {
+        let same_drop = match (self.drop, other.drop)
+        {
+            (Some(a), Some(b)) => ptr::fn_addr_eq(a, b),
+            (None, None) => true,
+            _ => false
+        };
+        self.name == other.name && self.layout == other.layout && same_drop
+    }
+}
+
+/// Sparse storage of runtime defined components as raw bytes, one allocation per component
+pub(crate) struct DynamicStore
+{
+    data: HashMap<usize, NonNull<u8>>,
+    ticks: HashMap<usize, ComponentTicks>,
+    descriptor: ComponentDescriptor,
+    change_tick: u64
+}
+
+// the store owns its allocations, the safety contract of ComponentDescriptor::new requires the bytes to be Send and Sync
+unsafe impl Send for DynamicStore {}
+unsafe impl Sync for DynamicStore {}
+
+impl DynamicStore
+{
+    pub(crate) fn new(descriptor: &ComponentDescriptor) -> Self
+    {
+        Self { data: HashMap::new(), ticks: HashMap::new(), descriptor: descriptor.clone(), change_tick: 0 }
+    }
+
+    pub(crate) fn descriptor(&self) -> &ComponentDescriptor
+    {
+        &self.descriptor
+    }
+
+    pub(crate) fn contains(&self, index: usize) -> bool
+    {
+        self.data.contains_key(&index)
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<*const u8>
+    {
+        self.data.get(&index).map(|data| data.as_ptr() as *const u8)
+    }
+
+    /// Returns the component at index, marking it changed at the current change tick
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<*mut u8>
+    {
+        let data = self.data.get(&index)?.as_ptr();
+        self.ticks.entry(index).or_default().changed = self.change_tick;
+        Some(data)
+    }
+
+    /// Moves the component data points to into the store at index, dropping the one it replaces.
+    /// Returns true if a component was replaced.
+    ///
+    /// # Safety
+    /// data must point to a valid component of the store's layout, which must not be used or dropped afterwards
+    pub(crate) unsafe fn insert(&mut self, index: usize, data: *const u8) -> bool
+    {
+        let layout = self.descriptor.layout;
+        let slot = if layout.size() == 0
+        {
+            NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap()
+        }
+        else
+        {
+            let slot = unsafe { alloc::alloc(layout) };
+            NonNull::new(slot).unwrap_or_else(|| alloc::handle_alloc_error(layout))
+        };
+        unsafe
+        {
+            ptr::copy_nonoverlapping(data, slot.as_ptr(), layout.size());
+            self.put(index, slot)
+        }
+    }
+
+    /// Stores a component allocated for the store's layout at index, dropping the one it replaces.
+    /// Returns true if a component was replaced.
+    ///
+    /// # Safety
+    /// slot must be allocated with the store's layout, or dangling for zero sized layouts, and hold a valid component
+    pub(crate) unsafe fn put(&mut self, index: usize, slot: NonNull<u8>) -> bool
+    {
+        // remove forgets the ticks, a replaced component keeps the tick it was added at
+        let added = self.ticks.get(&index).map(|ticks| ticks.added);
+        let replaced = self.remove(index);
+        self.data.insert(index, slot);
+
+        // replacing a component marks it changed, otherwise it is added
+        let ticks = self.ticks.entry(index).or_default();
+        ticks.added = added.filter(|_| replaced).unwrap_or(self.change_tick);
+        ticks.changed = self.change_tick;
+        replaced
+    }
+
+    /// Takes the component at index out of the store without dropping it, the caller owns its allocation afterwards
+    pub(crate) fn take(&mut self, index: usize) -> Option<NonNull<u8>>
+    {
+        self.ticks.remove(&index);
+        self.data.remove(&index)
+    }
+
+    /// Drops the component at index, returns false if there was none
+    pub(crate) fn remove(&mut self, index: usize) -> bool
+    {
+        self.ticks.remove(&index);
+        let Some(slot) = self.data.remove(&index) else
+        {
+            return false;
+        };
+
+        unsafe
+        {
+            if let Some(drop) = self.descriptor.drop
+            {
+                drop(slot.as_ptr());
+            }
+            if self.descriptor.layout.size() != 0
+            {
+                alloc::dealloc(slot.as_ptr(), self.descriptor.layout);
+            }
+        }
+        true
+    }
+}
+
+impl Drop for DynamicStore
+{
+    fn drop(&mut self)
+    {
+        let indices: Vec<usize> = self.data.keys().copied().collect();
+        for index in indices
+        {
+            self.remove(index);
+        }
+    }
+}
+
+impl ComponentStore for DynamicStore
+{
+    fn push_none(&mut self) {}
+
+    fn set_none(&mut self, index: usize)
+    {
+        self.remove(index);
+    }
+
+    fn resize_to_nones(&mut self, len: usize)
+    {
+        let indices: Vec<usize> = self.data.keys().copied().filter(|&index| index >= len).collect();
+        for index in indices
+        {
+            self.remove(index);
+        }
+    }
+
+    fn drop(&mut self, index: usize)
+    {
+        self.remove(index);
+    }
+
+    fn set_change_tick(&mut self, tick: u64)
+    {
+        self.change_tick = tick;
+    }
+
+    fn ticks(&self, index: usize) -> Option<ComponentTicks>
+    {
+        self.ticks.get(&index).copied()
+    }
+
+    fn take_boxed(&mut self, _index: usize) -> Option<Box<dyn Any + Send + Sync>>
+    {
+        // there is no Rust type to box the bytes as
+        None
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<*const u8>
+    {
+        self.get(index)
+    }
+
+    fn get_mut_ptr(&mut self, index: usize) -> Option<*mut u8>
+    {
+        self.get_mut(index)
+    }
+
+    fn as_any(&self) -> &dyn Any
+    {
+        self as &dyn std::any::Any
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    {
+        self as &mut dyn std::any::Any
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{component::{Component, ComponentId}, component_store::VecStore, query::DynamicQuery, registry::Registry};
+
+    use super::*;
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe fn count_drop(_data: *mut u8)
+    {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn dynamic_components()
+    {
+        let mut registry = Registry::new();
+        let velocity = registry.register_dynamic_component(unsafe { ComponentDescriptor::new("mod::Velocity", Layout::new::<[f32; 2]>(), Some(count_drop)) });
+        let tag = registry.register_dynamic_component(unsafe { ComponentDescriptor::new("mod::Tag", Layout::new::<()>(), None) });
+        assert_eq!(registry.component_info(velocity).unwrap().name(), "mod::Velocity");
+        assert!(registry.component_info(velocity).unwrap().type_id().is_none());
+
+        let e1 = registry.spawn(());
+        let e2 = registry.spawn(());
+        unsafe
+        {
+            assert!(registry.insert_by_id(e1, velocity, [1.0f32, 2.0].as_ptr() as *const u8));
+            assert!(registry.insert_by_id(e2, velocity, [3.0f32, 4.0].as_ptr() as *const u8));
+            assert!(registry.insert_by_id(e2, tag, [(); 0].as_ptr() as *const u8));
+        }
+        assert_eq!(DynamicQuery::new().with(velocity).get(&registry), vec![e1, e2]);
+        assert_eq!(DynamicQuery::new().with(velocity).with(tag).get(&registry), vec![e2]);
+
+        let data = registry.get_by_id_mut(e2, velocity).unwrap() as *mut [f32; 2];
+        unsafe { (*data)[0] = 5.0 };
+        let data = registry.get_by_id(e2, velocity).unwrap() as *const [f32; 2];
+        assert_eq!(unsafe { *data }, [5.0, 4.0]);
+
+        // removing and despawning drop the components
+        assert!(registry.remove_by_id(e1, velocity));
+        assert!(registry.get_by_id(e1, velocity).is_none());
+        registry.despawn(e2);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 2);
+        assert!(DynamicQuery::new().with(tag).get(&registry).is_empty());
+    }
+
+    #[test]
+    fn replace_keeps_added_tick()
+    {
+        let mut store = DynamicStore::new(&unsafe { ComponentDescriptor::new("mod::Health", Layout::new::<u32>(), None) });
+        store.set_change_tick(1);
+        assert!(!unsafe { store.insert(0, &5u32 as *const u32 as *const u8) });
+        store.set_change_tick(2);
+        assert!(unsafe { store.insert(0, &6u32 as *const u32 as *const u8) });
+        assert_eq!(store.ticks(0), Some(ComponentTicks { added: 1, changed: 2 }));
+        assert_eq!(unsafe { *(store.get(0).unwrap() as *const u32) }, 6);
+    }
+
+    #[test]
+    fn move_keeps_drop_fns_apart()
+    {
+        // count_drop is asserted on by dynamic_components, which may run at the same time
+        unsafe fn release(_data: *mut u8) {}
+
+        let tracked = unsafe { ComponentDescriptor::new("mod::Handle", Layout::new::<u32>(), Some(release)) };
+        let plain = unsafe { ComponentDescriptor::new("mod::Handle", Layout::new::<u32>(), None) };
+        let mut source = Registry::new();
+        let handle = source.register_dynamic_component(tracked);
+        let id = source.spawn(());
+        unsafe { source.insert_by_id(id, handle, &3u32 as *const u32 as *const u8) };
+
+        // a component with the same name and layout but another drop fn doesn't share the store
+        let mut target = Registry::new();
+        let other = target.register_dynamic_component(plain);
+        let moved = target.move_entities(&mut source, &[id]).map(id).unwrap();
+        assert!(!target.has_component_id(moved, other));
+        let infos: Vec<ComponentId> = target.component_infos().filter(|info| info.name() == "mod::Handle").map(|info| info.id()).collect();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(unsafe { *(target.get_by_id(moved, infos[1]).unwrap() as *const u32) }, 3);
+    }
+
+    struct Score(u32);
+
+    impl Component for Score
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    #[test]
+    fn rust_components_by_id()
+    {
+        let mut registry = Registry::new();
+        let id = registry.spawn((Score(1),));
+        let score = registry.component_id::<Score>().unwrap();
+        assert_eq!(registry.component_info(score).unwrap().size(), 4);
+
+        // the registry takes ownership, so the value must not be dropped here
+        let replacement = std::mem::ManuallyDrop::new(Score(7));
+        assert!(unsafe { registry.insert_by_id(id, score, &*replacement as *const Score as *const u8) });
+        assert_eq!(unsafe { (*(registry.get_by_id(id, score).unwrap() as *const Score)).0 }, 7);
+        assert_eq!(DynamicQuery::new().with(score).get(&registry), vec![id]);
+
+        assert!(registry.remove_by_id(id, score));
+        assert!(!registry.has_component::<Score>(id));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{any::TypeId, collections::HashSet};
+use std::{
+    any::TypeId, 
+    collections::HashSet, 
+    fmt,
+    sync::atomic::{AtomicIsize, Ordering}
+};
 
 
 // #[derive(Clone, PartialEq, Debug)]
@@ -16,7 +21,7 @@ use std::{any::TypeId, collections::HashSet};
 // }
 
 
-// pub struct EntityManager
+// pub(crate) struct EntityManager
 // {
 //     active_entities: Vec<EntityStatus>,
 //     dropped_ids: Vec<usize> 
@@ -88,21 +93,51 @@ use std::{any::TypeId, collections::HashSet};
 
 // }
 
-// TODO: Add parent / child Entity abiility
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq)]
 pub struct Entity
 {
     pub type_ids: HashSet<TypeId>,
     pub id: usize, // Id correlates to index position in EntityManager.active_entities
+    pub name: Option<String>, // Copy of the entity's Name component, for debugging
     // generation maybe not needed, commented out below
     // pub generation: u64, // Correlates to how many times the EntityManager has reused this ID
     pub is_active: bool // Flag to mark use
 }
 
-pub struct EntityManager
+impl fmt::Debug for Entity
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let mut debug = f.debug_struct("Entity");
+        debug.field("id", &self.id);
+        if let Some(name) = &self.name
+        {
+            debug.field("name", name);
+        }
+        debug.field("is_active", &self.is_active).field("type_ids", &self.type_ids).finish()
+    }
+}
+
+impl Entity
+{
+    /// Returns the id followed by the name if it has one, for messages
+    pub fn label(&self) -> String
+    {
+        match &self.name
+        {
+            Some(name) => format!("{} ({name:?})", self.id),
+            None => self.id.to_string()
+        }
+    }
+}
+
+pub(crate) struct EntityManager
 {
     pub(crate) active: Vec<Entity>,
-    pub(crate) dropped: Vec<usize> 
+    pub(crate) dropped: Vec<usize>,
+    // Number of dropped ids not handed out by reserve yet,
+    // goes negative by the number of new ids reserved past the end of active
+    free_cursor: AtomicIsize
 }
 
 impl EntityManager
@@ -112,13 +147,17 @@ impl EntityManager
     {
         Self {
             active: Vec::new(),
-            dropped: Vec::new()
+            dropped: Vec::new(),
+            free_cursor: AtomicIsize::new(0)
         }
     }
 
-    /// Activates a new entity or reuses an old deactivated one, returns id
+    /// Activates a new entity or reuses an old deactivated one, returns id.
+    /// Reserved entities must be flushed first, or their ids would be handed out twice.
     pub fn activate(&mut self) -> usize
     {
+        debug_assert!(!self.has_reserved(), "Reserved entities must be flushed through Registry::flush_reserved_entities first");
+
         // Previously used entity ID available
         if let Some(id) = self.dropped.pop()
         {
@@ -127,17 +166,144 @@ impl EntityManager
             entity.is_active = true;
             // entity.generation += 1; // add to the generation
             entity.type_ids = HashSet::new();
+            entity.name = None;
+            self.sync_free_cursor();
             return id;
         }
 
         // No previously used entity IDs available
         // first generation = 0
-        let entity = Entity { id: self.active.len(), type_ids: HashSet::new(), is_active: true };
+        let entity = Entity { id: self.active.len(), type_ids: HashSet::new(), name: None, is_active: true };
         let id = entity.id;
         self.active.push(entity);
         id
     }
 
+    /// Activates count entities, reusing deactivated ids first, returns the ids.
+    /// Reserved entities must be flushed first, or their ids would be handed out twice.
+    pub fn activate_many(&mut self, count: usize) -> Vec<usize>
+    {
+        debug_assert!(!self.has_reserved(), "Reserved entities must be flushed through Registry::flush_reserved_entities first");
+
+        let mut ids = Vec::with_capacity(count);
+        while ids.len() < count
+        {
+            match self.dropped.pop()
+            {
+                Some(id) =>
+                {
+                    let entity = &mut self.active[id];
+                    entity.is_active = true;
+                    entity.type_ids = HashSet::new();
+                    entity.name = None;
+                    ids.push(id);
+                },
+                None => break
+            }
+        }
+
+        // allocate the remaining entities in one go
+        let remaining = count - ids.len();
+        self.active.reserve(remaining);
+        for _ in 0..remaining
+        {
+            let id = self.active.len();
+            self.active.push(Entity { id, type_ids: HashSet::new(), name: None, is_active: true });
+            ids.push(id);
+        }
+
+        self.sync_free_cursor();
+        ids
+    }
+
+    /// Reserves an entity id through a shared reference, safe to call from many threads at once.
+    /// The entity is activated by flush_reserved.
+    pub fn reserve(&self) -> usize
+    {
+        let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
+        if cursor > 0
+        {
+            // hand out dropped ids from the back, like activate
+            self.dropped[cursor as usize - 1]
+        }
+        else
+        {
+            self.active.len() + cursor.unsigned_abs()
+        }
+    }
+
+    /// Returns true if ids were reserved since the last flush
+    pub fn has_reserved(&mut self) -> bool
+    {
+        *self.free_cursor.get_mut() != self.dropped.len() as isize
+    }
+
+    /// Activates every reserved entity, returns their ids in reservation order
+    pub fn flush_reserved(&mut self) -> Vec<usize>
+    {
+        let cursor = *self.free_cursor.get_mut();
+        let first_reused = cursor.max(0) as usize;
+        let mut ids: Vec<usize> = self.dropped.drain(first_reused..).rev().collect();
+        for &id in &ids
+        {
+            let entity = &mut self.active[id];
+            entity.is_active = true;
+            entity.type_ids = HashSet::new();
+            entity.name = None;
+        }
+
+        let new_count = cursor.min(0).unsigned_abs();
+        for _ in 0..new_count
+        {
+            let id = self.active.len();
+            self.active.push(Entity { id, type_ids: HashSet::new(), name: None, is_active: true });
+            ids.push(id);
+        }
+
+        self.sync_free_cursor();
+        ids
+    }
+
+    /// Returns a manager with the given slots and free list, discarding any reservations
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_parts(active: &[bool], dropped: Vec<usize>) -> Self
+    {
+        let active = active.iter().enumerate()
+            .map(|(id, &is_active)| Entity { id, type_ids: HashSet::new(), name: None, is_active })
+            .collect();
+        let mut entities = Self { active, dropped, free_cursor: AtomicIsize::new(0) };
+        entities.sync_free_cursor();
+        entities
+    }
+
+    /// Grows to slots entities, activates the spawned ids and replaces the free list, used when applying a delta
+    #[cfg(feature = "serde")]
+    pub(crate) fn apply_allocator(&mut self, slots: usize, dropped: Vec<usize>, spawned: &[usize])
+    {
+        while self.active.len() < slots
+        {
+            let id = self.active.len();
+            self.active.push(Entity { id, type_ids: HashSet::new(), name: None, is_active: false });
+        }
+        for &id in spawned
+        {
+            if let Some(entity) = self.active.get_mut(id)
+            {
+                entity.is_active = true;
+                entity.type_ids = HashSet::new();
+                entity.name = None;
+            }
+        }
+        self.dropped = dropped;
+        self.sync_free_cursor();
+    }
+
+    /// Resets the free cursor after the dropped ids changed
+    fn sync_free_cursor(&mut self)
+    {
+        *self.free_cursor.get_mut() = self.dropped.len() as isize;
+    }
+
     // /// Drops (or deactivates) an entity
     // pub fn drop(&mut self, entity: Entity)
     // {
@@ -153,9 +319,12 @@ impl EntityManager
     //     }
     // }
 
-    /// Drops (or deactivates) an entity
+    /// Drops (or deactivates) an entity.
+    /// Reserved entities must be flushed first, or the dropped id would be mixed up with a reserved one.
     pub fn drop(&mut self, id: usize)
     {
+        debug_assert!(!self.has_reserved(), "Reserved entities must be flushed through Registry::flush_reserved_entities first");
+
         // If entity exists in active entities
         if let Some(e) = self.active.get_mut(id)
         {
@@ -163,7 +332,10 @@ impl EntityManager
             if e.is_active 
             {
                 e.is_active = false;
+                e.type_ids.clear();
+                e.name = None;
                 self.dropped.push(e.id);
+                self.sync_free_cursor();
             }
         }
     }
@@ -193,6 +365,7 @@ impl EntityManager
     }
 
     /// Returns the id of the last deactivated entity
+    #[allow(dead_code)]
     pub fn deactive_last(&self) -> Option<usize>
     {
         self.dropped.last().copied()
@@ -217,8 +390,8 @@ impl EntityManager
         // Iterate over active entities
         for e in &self.active
         {
-            // If entity has all components
-            if type_ids.is_subset(&e.type_ids)
+            // If entity is active and has all components
+            if e.is_active && type_ids.is_subset(&e.type_ids)
             {
                 // Add entity id to ids vec
                 ids.push(e.id);
@@ -231,6 +404,13 @@ impl EntityManager
 
 }
 
+impl Default for EntityManager
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
 
 #[cfg(test)]
 mod tests{
@@ -253,6 +433,38 @@ mod tests{
         let e3 = entities.activate();
         let entity3 = entities.get(e3).unwrap();
 
-        assert_eq!(*entity3, Entity{id: 1, type_ids: HashSet::new(), is_active: true});
+        assert_eq!(*entity3, Entity{id: 1, type_ids: HashSet::new(), name: None, is_active: true});
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_reserve()
+    {
+        let mut entities = EntityManager::new();
+        let ids = entities.activate_many(4);
+        entities.drop(ids[1]);
+        entities.drop(ids[3]);
+
+        // dropped ids are handed out first, then new ids past the end
+        let reserved: Vec<usize> = (0..4).map(|_| entities.reserve()).collect();
+        assert_eq!(reserved, vec![3, 1, 4, 5]);
+        assert!(entities.has_reserved());
+        assert!(entities.get(4).is_none());
+
+        assert_eq!(entities.flush_reserved(), reserved);
+        assert!(!entities.has_reserved());
+        assert!(reserved.iter().all(|&id| entities.get(id).unwrap().is_active));
+        assert_eq!(entities.activate(), 6);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Reserved entities must be flushed")]
+    fn test_activate_requires_flush()
+    {
+        let mut entities = EntityManager::new();
+        entities.reserve();
+
+        // only the Registry can flush, since it grows the component stores for the new ids
+        entities.activate();
+    }
+}
@@ -1,10 +1,10 @@
 use std::{
-    any::{Any, TypeId}, 
+    any::TypeId, 
     collections::HashSet, 
 };
 
 use crate::{
-    component_store::VecStore, registry::Registry
+    bundle::Bundle, component::Component, registry::Registry
 };
 
 
@@ -23,13 +23,17 @@ impl<'a> EntityBuilder<'a>
     /// Creates a new EntityBuilder
     pub fn new(registry: &'a mut Registry) -> Self
     {
+        registry.flush_reserved_entities();
+
+        // check before activating, activate pops the deactivated id being reused
+        let is_reused = !registry.entities.is_deactivated_empty();
         let id = registry.entities.activate();
 
-        // if deactiveated entities exist to use, use that
-        if !registry.entities.is_deactivated_empty()
+        // if a deactivated entity was reused, clear its slot
+        if is_reused
         {
-            registry.components.iter_mut().for_each(
-                | (_type_id, comps) |
+            registry.stores_mut().for_each(
+                | comps |
                 {
                     comps.set_none(id);
                 }
@@ -37,8 +41,8 @@ impl<'a> EntityBuilder<'a>
         } 
         else // allocate entity data with push to end
         { 
-            registry.components.iter_mut().for_each(
-                | (_type_id, comps) |
+            registry.stores_mut().for_each(
+                | comps |
                 {
                     comps.push_none();
                 }
@@ -49,26 +53,11 @@ impl<'a> EntityBuilder<'a>
     }
 
     /// Adds a component to the entity
-    pub fn with_component<T: Any + Send + Sync>(&mut self, data: T) -> &mut Self
+    pub fn with_component<T: Component>(&mut self, data: T) -> &mut Self
     {
         let type_id = TypeId::of::<T>();
-        // if component doesn't exist in registry, add it
-        if !self.registry.components.contains_key(&type_id)
-        {
-            self.registry.register_component::<T>();
-        }
-
-        if let Some(comps) = self.registry.components.get_mut(&type_id)
-        {
-            // downcast Any to VecStore<T>
-            if let Some(vstore) = comps.as_any_mut().downcast_mut::<VecStore<T>>()
-            {
-                if let Ok(mut component) = vstore.get_mut(self.id)
-                {
-                    *component = Some(data);
-                }
-            }
-        }
+        // registers the component if it doesn't exist in the registry and runs its hooks
+        self.registry.set_component(self.id, data);
 
         // add type_id to type_ids
         self.type_ids.insert(type_id);
@@ -76,12 +65,23 @@ impl<'a> EntityBuilder<'a>
         self
     }
 
+    /// Adds every component in the bundle to the entity
+    pub fn with_bundle<B: Bundle>(&mut self, bundle: B) -> &mut Self
+    {
+        B::register_components(self.registry);
+        bundle.insert_components(self.registry, self.id);
+        self.type_ids.extend(B::type_ids());
+
+        self
+    }
+
     /// Builds the Entity, stores it, and returns the id
     pub fn build(&mut self) -> usize
     {
         // let entity = Entity{ id: self.id, generation: self.generation, type_ids: self.type_ids.clone(), is_active: self.is_active };
+        // extended rather than replaced, on_add hooks may have added components of their own
         let entity = self.registry.entities.get_mut(self.id).unwrap();
-        entity.type_ids = self.type_ids.clone();
+        entity.type_ids.extend(self.type_ids.iter().copied());
         self.id
     }
 
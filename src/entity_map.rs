@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+/// Maps entity ids from one World, or a saved scene, to the ids they were given in another
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EntityMap
+{
+    map: HashMap<usize, usize>
+}
+
+impl EntityMap
+{
+    pub fn new() -> Self
+    {
+        Self { map: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, from: usize, to: usize)
+    {
+        self.map.insert(from, to);
+    }
+
+    /// Returns the id from was mapped to, or None if it wasn't mapped
+    pub fn map(&self, from: usize) -> Option<usize>
+    {
+        self.map.get(&from).copied()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.map.is_empty()
+    }
+
+    /// Iterates over every (from, to) pair
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_
+    {
+        self.map.iter().map(|(&from, &to)| (from, to))
+    }
+}
+
+/// Implemented by components and resources that store entity ids,
+/// so the ids can be updated when entities are loaded or moved into another World
+pub trait MapEntities
+{
+    /// Returns false if a stored id isn't in the map and the value can't be kept without it,
+    /// the value is then dropped instead of being inserted
+    fn map_entities(&mut self, map: &EntityMap) -> bool;
+}
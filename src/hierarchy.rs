@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::{component::Component, component_store::VecStore, entity_map::{EntityMap, MapEntities}, registry::Registry};
+
+pub struct HierarchyError
+{
+    pub(crate) message: String
+}
+
+impl fmt::Display for HierarchyError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for HierarchyError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Parent of an entity, set through set_parent and remove_parent, inserting it directly also lists the child in Children
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parent(pub(crate) usize);
+
+impl Parent
+{
+    /// Returns the id of the parent entity
+    pub fn id(&self) -> usize
+    {
+        self.0
+    }
+}
+
+impl Component for Parent
+{
+    type Storage = VecStore<Self>;
+
+    fn on_add(registry: &mut Registry, id: usize)
+    {
+        registry.attach_to_parent(id);
+    }
+
+    fn on_remove(registry: &mut Registry, id: usize)
+    {
+        registry.detach_from_parent(id);
+    }
+}
+
+/// Children of an entity in the order they were added, maintained by the World
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Children(pub(crate) Vec<usize>);
+
+impl Children
+{
+    /// Returns the ids of the child entities
+    pub fn ids(&self) -> &[usize]
+    {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.0.is_empty()
+    }
+}
+
+impl MapEntities for Parent
+{
+    fn map_entities(&mut self, map: &EntityMap) -> bool
+    {
+        map.map(self.0).map(|parent| self.0 = parent).is_some()
+    }
+}
+
+impl MapEntities for Children
+{
+    fn map_entities(&mut self, map: &EntityMap) -> bool
+    {
+        // children that weren't mapped stay behind
+        self.0 = self.0.iter().filter_map(|&child| map.map(child)).collect();
+        !self.0.is_empty()
+    }
+}
+
+impl Component for Children
+{
+    type Storage = VecStore<Self>;
+
+    fn on_remove(registry: &mut Registry, id: usize)
+    {
+        registry.orphan_children(id);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::{prefab::Prefab, world::World};
+
+    use super::*;
+
+    #[test]
+    fn test_set_parent()
+    {
+        let mut world = World::new();
+        let root = world.spawn((Node,));
+        let a = world.spawn((Node,));
+        let b = world.spawn((Node,));
+
+        world.set_parent(a, root).unwrap();
+        world.add_child(root, b).unwrap();
+        assert_eq!(world.parent(a), Some(root));
+        assert_eq!(world.children(root), vec![a, b]);
+
+        // moving a child detaches it from its old parent
+        world.set_parent(b, a).unwrap();
+        assert_eq!(world.children(root), vec![a]);
+        assert_eq!(world.children(a), vec![b]);
+        assert_eq!(world.hierarchy_order(), vec![root, a, b]);
+
+        assert_eq!(world.remove_parent(a), Some(root));
+        assert!(world.children(root).is_empty());
+        assert_eq!(world.query().with_component::<Children>().get(), vec![a]);
+    }
+
+    #[test]
+    fn test_prevent_cycles()
+    {
+        let mut world = World::new();
+        let a = world.spawn((Node,));
+        let b = world.spawn((Node,));
+        let c = world.spawn((Node,));
+        world.set_parent(b, a).unwrap();
+        world.set_parent(c, b).unwrap();
+
+        assert!(world.set_parent(a, c).is_err());
+        assert!(world.set_parent(a, a).is_err());
+        assert_eq!(world.parent(a), None);
+        assert_eq!(world.descendants(a), vec![b, c]);
+    }
+
+    #[test]
+    fn test_despawn_recursive()
+    {
+        let mut world = World::new();
+        let root = world.spawn((Node,));
+        let a = world.spawn((Node,));
+        let b = world.spawn((Node,));
+        let c = world.spawn((Node,));
+        world.set_parent(a, root).unwrap();
+        world.set_parent(b, a).unwrap();
+        world.set_parent(c, root).unwrap();
+
+        assert!(world.despawn_recursive(a));
+        assert!(!world.get_entity(a).unwrap().is_active);
+        assert!(!world.get_entity(b).unwrap().is_active);
+        assert_eq!(world.children(root), vec![c]);
+
+        // despawning a parent on its own leaves its children as roots
+        world.despawn(root);
+        assert_eq!(world.parent(c), None);
+        assert_eq!(world.hierarchy_order(), vec![c]);
+    }
+
+    #[test]
+    fn test_parent_inserted_directly()
+    {
+        let mut world = World::new();
+        world.register_cloneable::<Parent>();
+        let root = world.spawn((Node,));
+        let a = world.spawn((Node,));
+        world.set_parent(a, root).unwrap();
+
+        // inserting Parent outside set_parent still lists the child
+        let b = world.spawn((Node, Parent(root)));
+        let copy = world.clone_entity(a).unwrap();
+        let spawned = world.spawn_prefab(&Prefab::new().with(Parent(root)));
+        assert_eq!(world.children(root), vec![a, b, copy, spawned]);
+        assert_eq!(world.parent(copy), Some(root));
+
+        world.insert_bundle(b, (Parent(a),));
+        assert_eq!(world.children(root), vec![a, copy, spawned]);
+        assert_eq!(world.children(a), vec![b]);
+        assert_eq!(world.hierarchy_order(), vec![root, a, b, copy, spawned]);
+    }
+
+    struct Node;
+
+    impl Component for Node
+    {
+        type Storage = VecStore<Self>;
+    }
+}
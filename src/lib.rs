@@ -1,26 +1,89 @@
+// lets the derive macros refer to `::my_ecs` from inside this crate
+extern crate self as my_ecs;
+
+mod bundle;
+mod command;
+mod component;
+mod dynamic;
 mod entity;
 mod entity_builder;
+mod entity_map;
+mod hierarchy;
+mod name;
+mod prefab;
+mod reflect;
 mod component_store;
 mod resource;
+#[cfg(feature = "serde")]
+mod scene;
 mod registry;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod relation;
 mod system;
+#[cfg(feature = "transform")]
+mod transform;
 mod query;
+#[cfg(feature = "serde")]
+mod type_registry;
 mod world;
 
 // mod tuple_append;
 
+pub use bundle::Bundle;
+pub use command::{Command, CommandError, CommandQueue, Commands};
+pub use component::{Component, ComponentId, ComponentInfo};
+pub use component_store::{ComponentError, ComponentMut, ComponentStore, ComponentTicks, SparseStore, TypedStore, VecStore};
+pub use dynamic::ComponentDescriptor;
+pub use entity::Entity;
+pub use entity_builder::EntityBuilder;
+pub use entity_map::{EntityMap, MapEntities};
+pub use hierarchy::{Children, HierarchyError, Parent};
+pub use name::Name;
+pub use prefab::Prefab;
+pub use reflect::{Reflect, ReflectError};
+pub use query::{AnyOf, ChangeMarker, DynamicQuery, DynamicQueryItem, DynamicQueryItemMut, Mut, Or, QueryBuilder, QueryData, QueryFilter, With, Without};
+pub use registry::Registry;
+pub use relation::{Related, Relation, RelationCleanup, RelationError};
+pub use resource::{NonSend, NonSendMut, Res, ResMut, Resource, ResourceError, Resources};
+#[cfg(feature = "serde")]
+pub use scene::{Scene, SceneEntity, SceneError};
+#[cfg(feature = "serde")]
+pub use snapshot::{Delta, Snapshot};
+pub use system::{resource_added, resource_changed, resource_exists, Dispatch, ParamSystem, RunCondition, System, SystemParam};
+#[cfg(feature = "serde")]
+pub use type_registry::TypeRegistry;
+#[cfg(feature = "transform")]
+pub use transform::{GlobalTransform, LocalTransform, Mat4, Quat, TransformPropagation};
+pub use world::{FromWorld, World};
 
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+#[cfg(feature = "derive")]
+pub use my_ecs_macros::{Bundle, Component, Reflect, Resource, SystemParam};
+
+/// Commonly used types, meant to be glob imported with `use my_ecs::prelude::*;`
+pub mod prelude
+{
+    pub use crate::{
+        bundle::Bundle,
+        command::Commands,
+        component::Component,
+        component_store::{SparseStore, TypedStore, VecStore},
+        entity_builder::EntityBuilder,
+        entity_map::{EntityMap, MapEntities},
+        hierarchy::{Children, Parent},
+        name::Name,
+        prefab::Prefab,
+        query::{AnyOf, Mut, Or, QueryBuilder, QueryData, QueryFilter, With, Without},
+        reflect::Reflect,
+        relation::{Related, Relation, RelationCleanup},
+        resource::{NonSend, NonSendMut, Res, ResMut, Resource, ResourceError},
+        system::{resource_added, resource_changed, resource_exists, Dispatch, ParamSystem, RunCondition, System, SystemParam},
+        world::{FromWorld, World}
+    };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[cfg(feature = "transform")]
+    pub use crate::transform::{GlobalTransform, LocalTransform, TransformPropagation};
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
+    #[cfg(feature = "derive")]
+    pub use my_ecs_macros::{Bundle, Component, Reflect, Resource, SystemParam};
 }
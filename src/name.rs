@@ -0,0 +1,219 @@
+use std::{any::Any, fmt};
+
+use crate::{
+    component::Component,
+    component_store::{sealed::StoreWrite, ComponentError, ComponentMut, ComponentStore, ComponentTicks, RwLockReadGuard, RwLockWriteGuard, SparseStore, TypedStore},
+    registry::Registry
+};
+
+/// Human readable name of an entity, shown in its Debug output and in error messages.
+/// Names are indexed, see World::find_by_name. Rename an entity with World::set_name.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Name(String);
+
+impl Name
+{
+    pub fn new(name: impl Into<String>) -> Self
+    {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str
+    {
+        &self.0
+    }
+}
+
+impl fmt::Display for Name
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Name
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl From<&str> for Name
+{
+    fn from(name: &str) -> Self
+    {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Name
+{
+    fn from(name: String) -> Self
+    {
+        Self(name)
+    }
+}
+
+impl Component for Name
+{
+    type Storage = NameStore;
+
+    fn on_add(registry: &mut Registry, id: usize)
+    {
+        registry.index_name(id);
+    }
+
+    fn on_remove(registry: &mut Registry, id: usize)
+    {
+        registry.unindex_name(id);
+    }
+}
+
+/// Sparse storage of Names that refuses mutable access, so a rename can't bypass the name index.
+/// Public only because it is the Storage of Name, it isn't re-exported so other crates can't name it.
+#[derive(Default)]
+pub struct NameStore(SparseStore<Name>);
+
+impl NameStore
+{
+    fn immutable(index: usize) -> ComponentError
+    {
+        ComponentError { message: format!("Failed to get mut component my_ecs::Name of entity {index}: Names can't be mutated in place, use World::set_name") }
+    }
+}
+
+impl ComponentStore for NameStore
+{
+    fn push_none(&mut self)
+    {
+        self.0.push_none();
+    }
+
+    fn set_none(&mut self, index: usize)
+    {
+        self.0.set_none(index);
+    }
+
+    fn resize_to_nones(&mut self, len: usize)
+    {
+        self.0.resize_to_nones(len);
+    }
+
+    fn drop(&mut self, index: usize)
+    {
+        ComponentStore::drop(&mut self.0, index);
+    }
+
+    fn set_change_tick(&mut self, tick: u64)
+    {
+        self.0.set_change_tick(tick);
+    }
+
+    fn ticks(&self, index: usize) -> Option<ComponentTicks>
+    {
+        self.0.ticks(index)
+    }
+
+    fn take_boxed(&mut self, index: usize) -> Option<Box<dyn Any + Send + Sync>>
+    {
+        self.0.take_boxed(index)
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<*const u8>
+    {
+        self.0.get_ptr(index)
+    }
+
+    fn get_mut_ptr(&mut self, _index: usize) -> Option<*mut u8>
+    {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any
+    {
+        self as &dyn std::any::Any
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any
+    {
+        self as &mut dyn std::any::Any
+    }
+}
+
+impl TypedStore<Name> for NameStore
+{
+    fn get(&self, index: usize) -> Result<RwLockReadGuard<'_, Option<Name>>, ComponentError>
+    {
+        self.0.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Result<ComponentMut<'_, Name>, ComponentError>
+    {
+        Err(Self::immutable(index))
+    }
+
+    fn insert(&mut self, index: usize, value: Name)
+    {
+        self.0.insert(index, value);
+    }
+
+    fn mark_changed(&mut self, index: usize)
+    {
+        self.0.mark_changed(index);
+    }
+}
+
+impl StoreWrite<Name> for NameStore
+{
+    fn write(&self, index: usize) -> Result<RwLockWriteGuard<'_, Option<Name>>, ComponentError>
+    {
+        Err(Self::immutable(index))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::world::World;
+
+    use super::*;
+
+    #[test]
+    fn test_find_by_name()
+    {
+        let mut world = World::new();
+        let player = world.spawn((Name::new("player"),));
+        let enemy = world.spawn((Name::new("enemy"),));
+        assert_eq!(world.find_by_name("player"), Some(player));
+        assert_eq!(world.find_by_name("enemy"), Some(enemy));
+
+        // renaming and despawning keep the index up to date
+        world.set_name(enemy, "boss");
+        assert!(world.get_components_mut::<Name>().unwrap().get_mut(enemy).is_err());
+        assert_eq!(world.find_by_name("enemy"), None);
+        assert_eq!(world.find_by_name("boss"), Some(enemy));
+        world.despawn(player);
+        assert_eq!(world.find_by_name("player"), None);
+        assert_eq!(world.get_entity(player).unwrap().name, None);
+    }
+
+    #[test]
+    fn test_debug_labels()
+    {
+        let mut world = World::new();
+        let root = world.spawn((Name::new("root"),));
+        let child = world.spawn((Name::new("child"),));
+        let unnamed = world.create_entity().build();
+
+        let debug = format!("{:?}", world.get_entity(root).unwrap());
+        assert!(debug.starts_with("Entity { id: 0, name: \"root\", is_active: true"), "{debug}");
+        assert!(!format!("{:?}", world.get_entity(unnamed).unwrap()).contains("name"));
+
+        world.set_parent(child, root).unwrap();
+        let err = world.set_parent(root, child).unwrap_err();
+        assert!(err.to_string().contains("1 (\"child\") is a descendant of 0 (\"root\")"), "{err}");
+    }
+}
@@ -0,0 +1,128 @@
+use std::any::TypeId;
+
+use crate::{component::Component, registry::Registry};
+
+/// Adds one recorded component to an entity
+type PrefabInsert = Box<dyn Fn(&mut Registry, usize) + Send + Sync>;
+
+/// Template of component values that can be spawned repeatedly
+#[derive(Default)]
+pub struct Prefab
+{
+    components: Vec<(TypeId, PrefabInsert)>
+}
+
+impl Prefab
+{
+    pub fn new() -> Self
+    {
+        Self { components: Vec::new() }
+    }
+
+    /// Records a component value, replacing a recorded value of the same type
+    pub fn with<T: Component + Clone>(mut self, component: T) -> Self
+    {
+        let type_id = TypeId::of::<T>();
+        self.components.retain(|(recorded, _)| *recorded != type_id);
+        self.components.push((type_id, Box::new(move | registry: &mut Registry, id: usize |
+        {
+            registry.insert_bundle(id, (component.clone(),));
+        })));
+        self
+    }
+
+    /// Returns true if a component of type T is recorded
+    pub fn contains<T: Component>(&self) -> bool
+    {
+        self.components.iter().any(|(recorded, _)| *recorded == TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.components.is_empty()
+    }
+
+    /// Adds a clone of every recorded component whose type isn't in skip to an existing entity
+    pub(crate) fn insert_components(&self, registry: &mut Registry, id: usize, skip: &[TypeId])
+    {
+        for (type_id, insert) in &self.components
+        {
+            if !skip.contains(type_id)
+            {
+                insert(registry, id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::component_store::VecStore;
+    use crate::name::Name;
+    use crate::world::World;
+
+    use super::*;
+
+    #[test]
+    fn test_clone_entity()
+    {
+        let mut world = World::new();
+        world.register_cloneable::<Health>();
+        world.register_cloneable::<Name>();
+        let original = world.spawn((Health{ value: 30 }, Name::new("grunt"), Marker));
+
+        let copy = world.clone_entity(original).unwrap();
+        assert_ne!(copy, original);
+        let healths = world.get_components::<Health>().unwrap();
+        assert_eq!(*healths.get(copy).unwrap(), Some(Health{ value: 30 }));
+        // hooks run for cloned components, components that didn't opt in are skipped
+        assert_eq!(world.find_all_by_name("grunt"), vec![original, copy]);
+        assert_eq!(world.query().with_component::<Marker>().get(), vec![original]);
+
+        world.despawn(original);
+        assert!(world.clone_entity(original).is_none());
+    }
+
+    #[test]
+    fn test_prefab_overrides()
+    {
+        let mut world = World::new();
+        let prefab = Prefab::new()
+            .with(Health{ value: 10 })
+            .with(Name::new("enemy"))
+            .with(Health{ value: 20 });
+        assert_eq!(prefab.len(), 2);
+
+        let a = world.spawn_prefab(&prefab);
+        let b = world.spawn_prefab_with(&prefab, (Health{ value: 99 },));
+
+        let healths = world.get_components::<Health>().unwrap();
+        assert_eq!(*healths.get(a).unwrap(), Some(Health{ value: 20 }));
+        assert_eq!(*healths.get(b).unwrap(), Some(Health{ value: 99 }));
+        assert_eq!(world.find_all_by_name("enemy"), vec![a, b]);
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Health
+    {
+        value: u32
+    }
+
+    impl Component for Health
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    struct Marker;
+
+    impl Component for Marker
+    {
+        type Storage = VecStore<Self>;
+    }
+}
@@ -1,12 +1,13 @@
-use std::{any::{Any, TypeId}, collections::HashSet};
+use std::{any::TypeId, collections::HashSet, marker::PhantomData, ops::{Deref, DerefMut}, sync::{RwLockReadGuard, RwLockWriteGuard}};
 
-use crate::{component_store::VecStore, registry::Registry};
+use crate::{component::{Component, ComponentId}, component_store::{sealed::StoreWrite, TypedStore}, registry::Registry};
 
 
 pub struct QueryBuilder<'a>
 {
     // stores: Vec<&'a dyn Any>,
     types: HashSet<TypeId>,
+    filters: Vec<fn(&Registry, usize) -> bool>, // per entity filters, such as change detection
     registry: &'a Registry
 }
 
@@ -14,10 +15,10 @@ impl<'a> QueryBuilder<'a>
 {
     pub fn new(registry: &'a Registry) -> Self
     {
-        Self { types: HashSet::new(), registry }
+        Self { types: HashSet::new(), filters: Vec::new(), registry }
     }
 
-    pub fn with_component<T: Any>(&mut self) -> &mut Self
+    pub fn with_component<T: Component>(&mut self) -> &mut Self
     {
         let type_id = TypeId::of::<T>();
 
@@ -36,9 +37,552 @@ impl<'a> QueryBuilder<'a>
         self
     }
 
+    /// Only matches entities passing the filter F, such as `Or<(With<Enemy>, With<Neutral>)>`
+    pub fn filter<F: QueryFilter>(&mut self) -> &mut Self
+    {
+        self.filters.push(F::matches);
+        self
+    }
+
+    /// Only matches entities whose component of type T was added since the last change tick
+    pub fn added<T: Component>(&mut self) -> &mut Self
+    {
+        self.with_component::<T>();
+        self.filters.push(| registry: &Registry, id: usize | registry.is_component_added::<T>(id));
+        self
+    }
+
+    /// Only matches entities whose component of type T was added or mutably accessed since the last change tick
+    pub fn changed<T: Component>(&mut self) -> &mut Self
+    {
+        self.with_component::<T>();
+        self.filters.push(| registry: &Registry, id: usize | registry.is_component_changed::<T>(id));
+        self
+    }
+
     pub fn get(&self) -> Vec<usize>
     {
-        self.registry.get_entity_ids(&self.types)
+        let mut ids = self.registry.get_entity_ids(&self.types);
+        ids.retain(|&id| self.filters.iter().all(|filter| filter(self.registry, id)));
+        ids
+    }
+
+}
+
+/// A query built from component ids at runtime, for editors and scripting bindings.
+/// Matches entities with every included component and none of the excluded ones,
+/// optional components are fetched when present without affecting which entities match.
+/// It doesn't borrow the registry, so it can be kept and run again.
+#[derive(Clone, Default, Debug)]
+pub struct DynamicQuery
+{
+    include: Vec<ComponentKey>,
+    exclude: Vec<ComponentKey>,
+    optional: Vec<ComponentKey>
+}
+
+/// A component a DynamicQuery refers to, Rust types are looked up when the query runs
+#[derive(Clone, Copy, Debug)]
+enum ComponentKey
+{
+    Id(ComponentId),
+    Type(TypeId)
+}
+
+impl ComponentKey
+{
+    fn resolve(self, registry: &Registry) -> Option<ComponentId>
+    {
+        match self
+        {
+            ComponentKey::Id(id) => registry.component_info(id).map(|info| info.id()),
+            ComponentKey::Type(type_id) => registry.component_id_of(type_id)
+        }
+    }
+}
+
+/// An entity matched by a DynamicQuery with pointers to its components,
+/// valid until a component of the entity is next inserted or removed
+#[derive(Clone, PartialEq, Debug)]
+pub struct DynamicQueryItem
+{
+    pub entity: usize,
+    /// One pointer per included component, in the order they were added to the query
+    pub components: Vec<*const u8>,
+    /// One pointer per optional component, None where the entity doesn't have it
+    pub optional: Vec<Option<*const u8>>
+}
+
+/// An entity matched by DynamicQuery::fetch_mut with mutable pointers to its components,
+/// which were all marked changed. Valid until a component of the entity is next inserted or removed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DynamicQueryItemMut
+{
+    pub entity: usize,
+    /// One pointer per included component, in the order they were added to the query
+    pub components: Vec<*mut u8>,
+    /// One pointer per optional component, None where the entity doesn't have it
+    pub optional: Vec<Option<*mut u8>>
+}
+
+impl DynamicQuery
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Only matches entities with the component registered under id
+    pub fn with(&mut self, id: ComponentId) -> &mut Self
+    {
+        self.include.push(ComponentKey::Id(id));
+        self
+    }
+
+    /// Only matches entities without the component registered under id
+    pub fn without(&mut self, id: ComponentId) -> &mut Self
+    {
+        self.exclude.push(ComponentKey::Id(id));
+        self
+    }
+
+    /// Fetches the component registered under id for entities that have it
+    pub fn optional(&mut self, id: ComponentId) -> &mut Self
+    {
+        self.optional.push(ComponentKey::Id(id));
+        self
+    }
+
+    /// Only matches entities with the Rust component type_id, matches nothing if it isn't registered
+    pub fn with_type_id(&mut self, type_id: TypeId) -> &mut Self
+    {
+        self.include.push(ComponentKey::Type(type_id));
+        self
     }
 
-}
\ No newline at end of file
+    /// Only matches entities without the Rust component type_id
+    pub fn without_type_id(&mut self, type_id: TypeId) -> &mut Self
+    {
+        self.exclude.push(ComponentKey::Type(type_id));
+        self
+    }
+
+    /// Returns the ids of every matching entity
+    pub fn get(&self, registry: &Registry) -> Vec<usize>
+    {
+        // an included component that isn't registered matches nothing
+        let Some(include) = self.include.iter().map(|key| key.resolve(registry)).collect::<Option<Vec<_>>>() else
+        {
+            return Vec::new();
+        };
+        let exclude: Vec<ComponentId> = self.exclude.iter().filter_map(|key| key.resolve(registry)).collect();
+
+        // narrow down with the entities' type ids first, runtime defined components are checked per entity
+        let types: HashSet<TypeId> = include.iter()
+            .filter_map(|&id| registry.component_info(id)?.type_id())
+            .collect();
+        let mut ids = registry.get_entity_ids(&types);
+        ids.retain(|&id|
+            include.iter().all(|&component| registry.has_component_id(id, component))
+            && !exclude.iter().any(|&component| registry.has_component_id(id, component))
+        );
+        ids
+    }
+
+    /// Returns every matching entity with pointers to its included and optional components
+    pub fn fetch(&self, registry: &Registry) -> Vec<DynamicQueryItem>
+    {
+        let include = Self::resolved(&self.include, registry);
+        let optional = Self::resolved(&self.optional, registry);
+        self.get(registry).into_iter().filter_map(|entity|
+        {
+            let components = include.iter()
+                .map(|&component| registry.get_by_id(entity, component?))
+                .collect::<Option<Vec<_>>>()?;
+            let optional = optional.iter().map(|&component| registry.get_by_id(entity, component?)).collect();
+            Some(DynamicQueryItem { entity, components, optional })
+        }).collect()
+    }
+
+    /// Returns every matching entity with mutable pointers to its included and optional components, marking them changed.
+    /// A component added to the query twice gets two pointers to the same data.
+    pub fn fetch_mut(&self, registry: &mut Registry) -> Vec<DynamicQueryItemMut>
+    {
+        let include = Self::resolved(&self.include, registry);
+        let optional = Self::resolved(&self.optional, registry);
+        self.get(registry).into_iter().filter_map(|entity|
+        {
+            let components = include.iter()
+                .map(|&component| registry.get_by_id_mut(entity, component?))
+                .collect::<Option<Vec<_>>>()?;
+            let optional = optional.iter().map(|&component| registry.get_by_id_mut(entity, component?)).collect();
+            Some(DynamicQueryItemMut { entity, components, optional })
+        }).collect()
+    }
+
+    /// Looks up the ids of keys, None for components that aren't registered
+    fn resolved(keys: &[ComponentKey], registry: &Registry) -> Vec<Option<ComponentId>>
+    {
+        keys.iter().map(|key| key.resolve(registry)).collect()
+    }
+}
+
+/// What a typed query fetches for each entity, such as `(&Position, &mut Velocity, Option<&Mass>)`.
+/// Used with Registry::query_each, which calls a closure with the item of every matching entity.
+pub trait QueryData
+{
+    /// Locks held on the fetched components while an item borrows from them
+    type Fetch<'w>;
+
+    /// Passed to the query closure, borrowing from the Fetch
+    type Item<'a>;
+
+    /// Adds the component types every matching entity must have, to narrow down candidates
+    fn add_required(types: &mut HashSet<TypeId>);
+
+    /// Returns true if the entity has what the query fetches
+    fn matches(registry: &Registry, id: usize) -> bool;
+
+    /// Locks the components of a matching entity
+    fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>;
+
+    fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>;
+
+    /// Adds the component types read and written, to reject queries that would lock a component twice
+    fn add_access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>);
+
+    /// Adds a marker for every mutably fetched component of the entity id that was mutably dereferenced,
+    /// the markers are applied after the query ran
+    fn written(_fetch: &Self::Fetch<'_>, _id: usize, _changes: &mut Vec<ChangeMarker>) {}
+}
+
+/// Marks a component of an entity changed once the query that wrote it is done
+pub type ChangeMarker = (usize, fn(&mut Registry, usize));
+
+/// A component fetched by a `&mut T` query term.
+/// Mutably dereferencing it marks the component as changed.
+pub struct Mut<'a, T>
+{
+    value: &'a mut T,
+    written: &'a mut bool
+}
+
+impl<'a, T> Deref for Mut<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target
+    {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        *self.written = true;
+        self.value
+    }
+}
+
+impl<T: Component> QueryData for &T
+{
+    type Fetch<'w> = RwLockReadGuard<'w, Option<T>>;
+    type Item<'a> = &'a T;
+
+    fn add_required(types: &mut HashSet<TypeId>)
+    {
+        types.insert(TypeId::of::<T>());
+    }
+
+    fn matches(registry: &Registry, id: usize) -> bool
+    {
+        registry.has_component::<T>(id)
+    }
+
+    fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>
+    {
+        let component = registry.get_components::<T>()?.get(id).ok()?;
+        component.is_some().then_some(component)
+    }
+
+    fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>
+    {
+        fetch.as_ref().expect("fetched components are present")
+    }
+
+    fn add_access(reads: &mut Vec<TypeId>, _writes: &mut Vec<TypeId>)
+    {
+        reads.push(TypeId::of::<T>());
+    }
+}
+
+impl<T: Component> QueryData for &mut T
+{
+    type Fetch<'w> = (RwLockWriteGuard<'w, Option<T>>, bool);
+    type Item<'a> = Mut<'a, T>;
+
+    fn add_required(types: &mut HashSet<TypeId>)
+    {
+        types.insert(TypeId::of::<T>());
+    }
+
+    fn matches(registry: &Registry, id: usize) -> bool
+    {
+        registry.has_component::<T>(id)
+    }
+
+    fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>
+    {
+        let component = registry.get_components::<T>()?.write(id).ok()?;
+        component.is_some().then_some((component, false))
+    }
+
+    fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>
+    {
+        let (component, written) = fetch;
+        Mut { value: component.as_mut().expect("fetched components are present"), written }
+    }
+
+    fn add_access(_reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>)
+    {
+        writes.push(TypeId::of::<T>());
+    }
+
+    fn written(fetch: &Self::Fetch<'_>, id: usize, changes: &mut Vec<ChangeMarker>)
+    {
+        if fetch.1
+        {
+            changes.push((id, | registry: &mut Registry, id: usize |
+            {
+                if registry.has_component::<T>(id)
+                {
+                    if let Some(comps) = registry.get_components_mut::<T>()
+                    {
+                        comps.mark_changed(id);
+                    }
+                }
+            }));
+        }
+    }
+}
+
+/// Fetches the inner term for entities that have it and None for the rest, without affecting which entities match
+impl<Q: QueryData> QueryData for Option<Q>
+{
+    type Fetch<'w> = Option<Q::Fetch<'w>>;
+    type Item<'a> = Option<Q::Item<'a>>;
+
+    fn add_required(_types: &mut HashSet<TypeId>) {}
+
+    fn matches(_registry: &Registry, _id: usize) -> bool
+    {
+        true
+    }
+
+    fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>
+    {
+        Some(Q::matches(registry, id).then(|| Q::fetch(registry, id)).flatten())
+    }
+
+    fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>
+    {
+        fetch.as_mut().map(Q::item)
+    }
+
+    fn add_access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>)
+    {
+        Q::add_access(reads, writes);
+    }
+
+    fn written(fetch: &Self::Fetch<'_>, id: usize, changes: &mut Vec<ChangeMarker>)
+    {
+        if let Some(fetch) = fetch
+        {
+            Q::written(fetch, id, changes);
+        }
+    }
+}
+
+macro_rules! impl_query_data_for_tuple
+{
+    ($($name: ident),*) =>
+    {
+        impl<$($name: QueryData),*> QueryData for ($($name,)*)
+        {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+            type Item<'a> = ($($name::Item<'a>,)*);
+
+            fn add_required(types: &mut HashSet<TypeId>)
+            {
+                $($name::add_required(types);)*
+            }
+
+            fn matches(registry: &Registry, id: usize) -> bool
+            {
+                $($name::matches(registry, id))&&*
+            }
+
+            fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>
+            {
+                Some(($($name::fetch(registry, id)?,)*))
+            }
+
+            #[allow(non_snake_case)]
+            fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>
+            {
+                let ($($name,)*) = fetch;
+                ($($name::item($name),)*)
+            }
+
+            fn add_access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>)
+            {
+                $($name::add_access(reads, writes);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn written(fetch: &Self::Fetch<'_>, id: usize, changes: &mut Vec<ChangeMarker>)
+            {
+                let ($($name,)*) = fetch;
+                $($name::written($name, id, changes);)*
+            }
+        }
+    }
+}
+
+impl_query_data_for_tuple!(A);
+impl_query_data_for_tuple!(A, B);
+impl_query_data_for_tuple!(A, B, C);
+impl_query_data_for_tuple!(A, B, C, D);
+impl_query_data_for_tuple!(A, B, C, D, E);
+impl_query_data_for_tuple!(A, B, C, D, E, F);
+impl_query_data_for_tuple!(A, B, C, D, E, F, G);
+impl_query_data_for_tuple!(A, B, C, D, E, F, G, H);
+
+/// Fetches every inner term the entity has and None for the rest, matching entities with at least one of them.
+/// `AnyOf<(&A, &mut B)>` yields `(Option<&A>, Option<Mut<B>>)`.
+pub struct AnyOf<T>(PhantomData<T>);
+
+macro_rules! impl_any_of_for_tuple
+{
+    ($($name: ident),*) =>
+    {
+        impl<$($name: QueryData),*> QueryData for AnyOf<($($name,)*)>
+        {
+            type Fetch<'w> = ($(Option<$name::Fetch<'w>>,)*);
+            type Item<'a> = ($(Option<$name::Item<'a>>,)*);
+
+            fn add_required(_types: &mut HashSet<TypeId>) {}
+
+            fn matches(registry: &Registry, id: usize) -> bool
+            {
+                $($name::matches(registry, id))||*
+            }
+
+            fn fetch(registry: &Registry, id: usize) -> Option<Self::Fetch<'_>>
+            {
+                Some(($($name::matches(registry, id).then(|| $name::fetch(registry, id)).flatten(),)*))
+            }
+
+            #[allow(non_snake_case)]
+            fn item<'a>(fetch: &'a mut Self::Fetch<'_>) -> Self::Item<'a>
+            {
+                let ($($name,)*) = fetch;
+                ($($name.as_mut().map($name::item),)*)
+            }
+
+            fn add_access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>)
+            {
+                $($name::add_access(reads, writes);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn written(fetch: &Self::Fetch<'_>, id: usize, changes: &mut Vec<ChangeMarker>)
+            {
+                let ($($name,)*) = fetch;
+                $(if let Some($name) = $name { $name::written($name, id, changes); })*
+            }
+        }
+    }
+}
+
+impl_any_of_for_tuple!(A);
+impl_any_of_for_tuple!(A, B);
+impl_any_of_for_tuple!(A, B, C);
+impl_any_of_for_tuple!(A, B, C, D);
+impl_any_of_for_tuple!(A, B, C, D, E);
+impl_any_of_for_tuple!(A, B, C, D, E, F);
+impl_any_of_for_tuple!(A, B, C, D, E, F, G);
+impl_any_of_for_tuple!(A, B, C, D, E, F, G, H);
+
+/// A condition entities must meet to match a query, without fetching anything.
+/// Tuples match when all of their filters do, `()` matches every entity.
+pub trait QueryFilter
+{
+    fn matches(registry: &Registry, id: usize) -> bool;
+}
+
+/// Matches entities with a component of type T
+pub struct With<T>(PhantomData<T>);
+
+/// Matches entities without a component of type T
+pub struct Without<T>(PhantomData<T>);
+
+/// Matches entities passing at least one of the filters in a tuple, such as `Or<(With<A>, (With<B>, Without<C>))>`
+pub struct Or<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T>
+{
+    fn matches(registry: &Registry, id: usize) -> bool
+    {
+        registry.has_component::<T>(id)
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T>
+{
+    fn matches(registry: &Registry, id: usize) -> bool
+    {
+        !registry.has_component::<T>(id)
+    }
+}
+
+impl QueryFilter for ()
+{
+    fn matches(_registry: &Registry, _id: usize) -> bool
+    {
+        true
+    }
+}
+
+macro_rules! impl_query_filter_for_tuple
+{
+    ($($name: ident),*) =>
+    {
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*)
+        {
+            fn matches(registry: &Registry, id: usize) -> bool
+            {
+                $($name::matches(registry, id))&&*
+            }
+        }
+
+        impl<$($name: QueryFilter),*> QueryFilter for Or<($($name,)*)>
+        {
+            fn matches(registry: &Registry, id: usize) -> bool
+            {
+                $($name::matches(registry, id))||*
+            }
+        }
+    }
+}
+
+impl_query_filter_for_tuple!(A);
+impl_query_filter_for_tuple!(A, B);
+impl_query_filter_for_tuple!(A, B, C);
+impl_query_filter_for_tuple!(A, B, C, D);
+impl_query_filter_for_tuple!(A, B, C, D, E);
+impl_query_filter_for_tuple!(A, B, C, D, E, F);
+impl_query_filter_for_tuple!(A, B, C, D, E, F, G);
+impl_query_filter_for_tuple!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,198 @@
+use std::{any::{self, Any}, fmt};
+
+pub struct ReflectError
+{
+    pub(crate) message: String
+}
+
+impl fmt::Display for ReflectError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for ReflectError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A value whose fields can be listed, read and written by name at runtime, for inspectors and scripting.
+/// Implemented for primitives and String, usually derived for structs with `#[derive(Reflect)]`,
+/// where `#[reflect(skip)]` hides a field. Tuple struct fields are named "0", "1" and so on.
+pub trait Reflect: Any + Send + Sync
+{
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Returns the name of the type
+    fn type_name(&self) -> &'static str
+    {
+        any::type_name::<Self>()
+    }
+
+    /// Returns the names of the fields in declaration order, empty for values without fields
+    fn field_names(&self) -> &'static [&'static str]
+    {
+        &[]
+    }
+
+    fn field(&self, _name: &str) -> Option<&dyn Reflect>
+    {
+        None
+    }
+
+    fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect>
+    {
+        None
+    }
+}
+
+impl dyn Reflect
+{
+    pub fn is<T: Reflect>(&self) -> bool
+    {
+        self.as_any().is::<T>()
+    }
+
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T>
+    {
+        self.as_any().downcast_ref::<T>()
+    }
+
+    pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T>
+    {
+        self.as_any_mut().downcast_mut::<T>()
+    }
+
+    /// Iterates over every field with its name, in declaration order
+    pub fn fields(&self) -> impl Iterator<Item = (&'static str, &dyn Reflect)> + '_
+    {
+        self.field_names().iter().filter_map(|&name| Some((name, self.field(name)?)))
+    }
+
+    /// Returns the field called name if it is a T
+    pub fn get_field<T: Reflect>(&self, name: &str) -> Option<&T>
+    {
+        self.field(name)?.downcast_ref::<T>()
+    }
+
+    /// Replaces the value, fails if it isn't a T
+    pub fn set<T: Reflect>(&mut self, value: T) -> Result<(), ReflectError>
+    {
+        let type_name = self.type_name();
+        match self.downcast_mut::<T>()
+        {
+            Some(current) =>
+            {
+                *current = value;
+                Ok(())
+            },
+            None => Err(ReflectError { message: format!("Failed to set value of type {type_name}: expected a {type_name}, got a {}", any::type_name::<T>()) })
+        }
+    }
+
+    /// Replaces the field called name, fails if there is no such field or it isn't a T
+    pub fn set_field<T: Reflect>(&mut self, name: &str, value: T) -> Result<(), ReflectError>
+    {
+        let type_name = self.type_name();
+        let field = self.field_mut(name)
+            .ok_or_else(|| ReflectError { message: format!("Failed to set field {name} of {type_name}: no such field") })?;
+        field.set(value).map_err(|err| ReflectError { message: format!("Failed to set field {name} of {type_name}: {err}") })
+    }
+}
+
+macro_rules! impl_reflect_for_value
+{
+    ($($ty: ty),*) =>
+    {
+        $(
+            impl Reflect for $ty
+            {
+                fn as_any(&self) -> &dyn Any
+                {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any
+                {
+                    self
+                }
+            }
+        )*
+    }
+}
+
+impl_reflect_for_value!(bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, String);
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    struct Stats
+    {
+        health: u32,
+        label: String
+    }
+
+    impl Reflect for Stats
+    {
+        fn as_any(&self) -> &dyn Any
+        {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any
+        {
+            self
+        }
+
+        fn field_names(&self) -> &'static [&'static str]
+        {
+            &["health", "label"]
+        }
+
+        fn field(&self, name: &str) -> Option<&dyn Reflect>
+        {
+            match name
+            {
+                "health" => Some(&self.health),
+                "label" => Some(&self.label),
+                _ => None
+            }
+        }
+
+        fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect>
+        {
+            match name
+            {
+                "health" => Some(&mut self.health),
+                "label" => Some(&mut self.label),
+                _ => None
+            }
+        }
+    }
+
+    #[test]
+    fn fields_by_name()
+    {
+        let mut stats = Stats { health: 10, label: "orc".to_owned() };
+        let value: &mut dyn Reflect = &mut stats;
+
+        let names: Vec<(&str, &str)> = value.fields().map(|(name, field)| (name, field.type_name())).collect();
+        assert_eq!(names, vec![("health", "u32"), ("label", "alloc::string::String")]);
+        assert_eq!(value.get_field::<u32>("health"), Some(&10));
+        assert_eq!(value.get_field::<i32>("health"), None);
+
+        value.set_field("health", 25u32).unwrap();
+        assert!(value.set_field("health", 25i64).is_err());
+        assert!(value.set_field("speed", 1u32).is_err());
+        assert_eq!(stats.health, 25);
+    }
+}
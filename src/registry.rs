@@ -1,77 +1,1117 @@
 use std::{
     any::{Any, TypeId}, 
+    ptr,
     collections::{HashMap, HashSet}
 };
 
-use std::thread;
+use crate::{
+    bundle::Bundle,
+    component::{Component, ComponentId, ComponentInfo},
+    component_store::{ComponentStore, ComponentTicks, TypedStore}, 
+    dynamic::{ComponentDescriptor, DynamicStore},
+    entity::{Entity, EntityManager}, 
+    entity_builder::EntityBuilder, query::{QueryBuilder, QueryData, QueryFilter}, 
+    entity_map::{EntityMap, MapEntities},
+    hierarchy::{Children, HierarchyError, Parent},
+    name::Name,
+    prefab::Prefab,
+    reflect::Reflect,
+    relation::{Related, Relation, RelationCleanup, RelationError},
+    // query::QueryBuilder
+};
+
+pub struct Registry
+{
+    pub(crate) components: HashMap<TypeId, Box<dyn ComponentStore>>,
+    pub(crate) entities: EntityManager,
+    component_fns: HashMap<TypeId, ComponentFns>, // type erased operations of every registered component
+    map_entities_fns: HashMap<TypeId, fn(&mut BoxedComponent, &EntityMap) -> bool>, // remaps the entity ids stored in a component
+    dynamic_components: HashMap<ComponentId, DynamicStore>, // stores of components defined at runtime
+    infos: Vec<ComponentInfo>, // name and layout of every registered component, indexed by ComponentId
+    component_ids: HashMap<TypeId, ComponentId>, // ComponentId of every registered Rust component
+    reflect_fns: HashMap<TypeId, ReflectFns>, // access to components registered with register_reflect
+    relation_sources: HashMap<usize, Vec<RelationSource>>, // relations pointing at each target entity
+    names: HashMap<String, Vec<usize>>, // entities with each Name, in the order they were named
+    clone_fns: HashMap<TypeId, fn(&mut Registry, usize, usize)>, // copies a cloneable component from one entity to another
+    pub(crate) change_tick: u64, // tick added and mutated components are marked with
+    pub(crate) last_change_tick: u64 // tick changes are detected relative to
+}
+
+impl Registry
+{
+    /// Creates a new registry
+    pub fn new() -> Self
+    {
+        let mut registry = Self {
+            components: HashMap::new(),
+            entities: EntityManager::new(),
+            component_fns: HashMap::new(),
+            map_entities_fns: HashMap::new(),
+            dynamic_components: HashMap::new(),
+            infos: Vec::new(),
+            component_ids: HashMap::new(),
+            reflect_fns: HashMap::new(),
+            relation_sources: HashMap::new(),
+            names: HashMap::new(),
+            clone_fns: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0
+        };
+        registry.register_map_entities::<Parent>();
+        registry.register_map_entities::<Children>();
+        registry
+    }
+
+    /// Adds a new component to the registry
+    pub fn register_component<T: Component>(&mut self)
+    {
+        let type_id = TypeId::of::<T>();
+        let mut comps = T::Storage::default();
+        comps.set_change_tick(self.change_tick);
+        // if entities already exist, populate the Vec with None
+        if !self.entities.is_activated_empty()
+        {
+            comps.resize_to_nones(self.entities.activated_size());
+        }
+
+        // let b_comps: Box<dyn ComponentStore + Send + Sync> = Box::new(comps);
+        self.components.insert(type_id, Box::new(comps));
+        self.component_fns.insert(type_id, ComponentFns {
+            on_remove: T::on_remove,
+            insert: | registry: &mut Registry, id: usize, component: BoxedComponent |
+            {
+                if let Ok(component) = component.downcast::<T>()
+                {
+                    registry.insert_bundle(id, (*component,));
+                }
+            },
+            insert_raw: | registry: &mut Registry, id: usize, data: *const u8 |
+            {
+                let component = unsafe { ptr::read(data as *const T) };
+                registry.insert_bundle(id, (component,));
+            },
+            remove: | registry: &mut Registry, id: usize | registry.remove_bundle::<(T,)>(id).is_some()
+        });
+        self.set_info(ComponentInfo::of::<T>(self.next_component_id(type_id), self.reflect_fns.contains_key(&type_id)));
+        T::on_register(self);
+    }
+
+    /// Registers a component type defined at runtime, returns the id to access it by
+    pub fn register_dynamic_component(&mut self, descriptor: ComponentDescriptor) -> ComponentId
+    {
+        let id = ComponentId(self.infos.len());
+        let mut comps = DynamicStore::new(&descriptor);
+        comps.set_change_tick(self.change_tick);
+        self.dynamic_components.insert(id, comps);
+        self.infos.push(ComponentInfo::dynamic(id, &descriptor));
+        id
+    }
+
+    /// Returns the runtime defined component registered with the same name, layout and drop fn as descriptor,
+    /// registering it if there is none
+    fn dynamic_component_id(&mut self, descriptor: &ComponentDescriptor) -> ComponentId
+    {
+        let existing = self.dynamic_components.iter()
+            .filter(|(_, comps)| comps.descriptor().matches(descriptor))
+            .map(|(&id, _)| id)
+            .min();
+        existing.unwrap_or_else(|| self.register_dynamic_component(descriptor.clone()))
+    }
+
+    /// Returns the id a Rust component type is registered under, or the next free id
+    fn next_component_id(&self, type_id: TypeId) -> ComponentId
+    {
+        self.component_ids.get(&type_id).copied().unwrap_or(ComponentId(self.infos.len()))
+    }
+
+    /// Records the info of a Rust component type, replacing the one it was registered with before
+    fn set_info(&mut self, info: ComponentInfo)
+    {
+        let id = info.id();
+        if let Some(type_id) = info.type_id()
+        {
+            self.component_ids.insert(type_id, id);
+        }
+
+        if id.0 < self.infos.len()
+        {
+            self.infos[id.0] = info;
+        }
+        else
+        {
+            self.infos.push(info);
+        }
+    }
+
+    /// Makes components of type T accessible through Reflect, registering the component itself if needed
+    pub fn register_reflect<T: Component + Reflect>(&mut self)
+    {
+        let type_id = TypeId::of::<T>();
+        self.reflect_fns.insert(type_id, ReflectFns {
+            get: | registry: &Registry, id: usize, f: &mut dyn FnMut(&dyn Reflect) |
+            {
+                if let Some(component) = registry.get_components::<T>().and_then(|comps| comps.get(id).ok())
+                {
+                    if let Some(component) = component.as_ref()
+                    {
+                        f(component);
+                    }
+                }
+            },
+            get_mut: | registry: &mut Registry, id: usize, f: &mut dyn FnMut(&mut dyn Reflect) |
+            {
+                if !registry.has_component::<T>(id)
+                {
+                    return;
+                }
+                if let Some(mut component) = registry.get_components_mut::<T>().and_then(|comps| comps.get_mut(id).ok())
+                {
+                    if let Some(component) = component.as_mut()
+                    {
+                        f(component);
+                    }
+                }
+            }
+        });
+
+        <(T,)>::register_components(self);
+        self.set_info(ComponentInfo::of::<T>(self.next_component_id(type_id), true));
+    }
+
+    /// Returns the id the component type T is registered under
+    pub fn component_id<T: Component>(&self) -> Option<ComponentId>
+    {
+        self.component_id_of(TypeId::of::<T>())
+    }
+
+    /// Returns the id the Rust component type_id is registered under
+    pub fn component_id_of(&self, type_id: TypeId) -> Option<ComponentId>
+    {
+        self.component_ids.get(&type_id).copied()
+    }
+
+    /// Returns the name and layout of a registered component type
+    pub fn component_info(&self, id: ComponentId) -> Option<&ComponentInfo>
+    {
+        self.infos.get(id.0)
+    }
+
+    /// Returns the registered component type with the given name, the full type name for Rust components
+    pub fn component_info_by_name(&self, name: &str) -> Option<&ComponentInfo>
+    {
+        self.infos.iter().find(|info| info.name() == name)
+    }
+
+    /// Iterates over every registered component type, in the order they were registered
+    pub fn component_infos(&self) -> impl Iterator<Item = &ComponentInfo> + '_
+    {
+        self.infos.iter()
+    }
+
+    /// Returns true if the entity id has the component registered under component
+    pub fn has_component_id(&self, id: usize, component: ComponentId) -> bool
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return false;
+        }
+
+        match self.infos.get(component.0).and_then(ComponentInfo::type_id)
+        {
+            Some(type_id) => self.entities.get(id).is_some_and(|e| e.type_ids.contains(&type_id)),
+            None => self.dynamic_components.get(&component).is_some_and(|comps| comps.contains(id))
+        }
+    }
+
+    /// Returns a pointer to the component registered under component on an entity.
+    /// The pointer is valid until the component is next inserted or removed.
+    pub fn get_by_id(&self, id: usize, component: ComponentId) -> Option<*const u8>
+    {
+        if !self.has_component_id(id, component)
+        {
+            return None;
+        }
+        self.store_by_id(component)?.get_ptr(id)
+    }
+
+    /// Returns a mutable pointer to the component registered under component on an entity, marking it changed
+    pub fn get_by_id_mut(&mut self, id: usize, component: ComponentId) -> Option<*mut u8>
+    {
+        if !self.has_component_id(id, component)
+        {
+            return None;
+        }
+
+        match self.infos.get(component.0)?.type_id()
+        {
+            Some(type_id) => self.components.get_mut(&type_id)?.get_mut_ptr(id),
+            None => self.dynamic_components.get_mut(&component)?.get_mut(id)
+        }
+    }
+
+    /// Moves the component data points to onto an entity, replacing the one it had, runs hooks of Rust components.
+    /// Returns false if the entity isn't active or component isn't registered, data is left untouched then.
+    ///
+    /// # Safety
+    /// data must point to a valid value of the component type, which must not be used or dropped afterwards
+    pub unsafe fn insert_by_id(&mut self, id: usize, component: ComponentId, data: *const u8) -> bool
+    {
+        self.flush_reserved_entities();
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return false;
+        }
+
+        let Some(info) = self.infos.get(component.0) else
+        {
+            return false;
+        };
+        match info.type_id()
+        {
+            Some(type_id) =>
+            {
+                let Some(insert_raw) = self.component_fns.get(&type_id).map(|fns| fns.insert_raw) else
+                {
+                    return false;
+                };
+                unsafe { insert_raw(self, id, data) };
+            },
+            None =>
+            {
+                let Some(comps) = self.dynamic_components.get_mut(&component) else
+                {
+                    return false;
+                };
+                unsafe { comps.insert(id, data) };
+            }
+        }
+        true
+    }
+
+    /// Drops the component registered under component from an entity, returns false if it had none
+    pub fn remove_by_id(&mut self, id: usize, component: ComponentId) -> bool
+    {
+        if !self.has_component_id(id, component)
+        {
+            return false;
+        }
+
+        match self.infos.get(component.0).and_then(ComponentInfo::type_id)
+        {
+            Some(type_id) =>
+            {
+                let remove = self.component_fns.get(&type_id).map(|fns| fns.remove);
+                remove.is_some_and(|remove| remove(self, id))
+            },
+            None => self.dynamic_components.get_mut(&component).is_some_and(|comps| comps.remove(id))
+        }
+    }
+
+    /// Returns the store of the component registered under component
+    fn store_by_id(&self, component: ComponentId) -> Option<&dyn ComponentStore>
+    {
+        match self.infos.get(component.0)?.type_id()
+        {
+            Some(type_id) => self.components.get(&type_id).map(|comps| comps.as_ref()),
+            None => self.dynamic_components.get(&component).map(|comps| comps as &dyn ComponentStore)
+        }
+    }
+
+    /// Iterates over the store of every Rust and runtime defined component
+    pub(crate) fn stores_mut(&mut self) -> impl Iterator<Item = &mut dyn ComponentStore> + '_
+    {
+        let dynamic = self.dynamic_components.values_mut().map(|comps| comps as &mut dyn ComponentStore);
+        self.components.values_mut().map(|comps| comps.as_mut() as &mut dyn ComponentStore).chain(dynamic)
+    }
+
+    /// Calls f with the component registered under component on an entity, returns None if the entity doesn't have one
+    /// or the type wasn't registered with register_reflect
+    pub fn reflect<R>(&self, id: usize, component: ComponentId, f: impl FnOnce(&dyn Reflect) -> R) -> Option<R>
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return None;
+        }
+
+        let type_id = self.infos.get(component.0)?.type_id()?;
+        let get = self.reflect_fns.get(&type_id)?.get;
+        let mut f = Some(f);
+        let mut result = None;
+        get(self, id, &mut |component| result = f.take().map(|f| f(component)));
+        result
+    }
+
+    /// Calls f with the component registered under component on an entity mutably, marking it changed.
+    /// Returns None if the entity doesn't have one or the type wasn't registered with register_reflect.
+    pub fn reflect_mut<R>(&mut self, id: usize, component: ComponentId, f: impl FnOnce(&mut dyn Reflect) -> R) -> Option<R>
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return None;
+        }
+
+        let type_id = self.infos.get(component.0)?.type_id()?;
+        let get_mut = self.reflect_fns.get(&type_id)?.get_mut;
+        let mut f = Some(f);
+        let mut result = None;
+        get_mut(self, id, &mut |component| result = f.take().map(|f| f(component)));
+        result
+    }
+
+    /// Remaps the entity ids stored in components or resources of type T
+    /// when they are moved in from another registry or loaded from a scene
+    pub fn register_map_entities<T: MapEntities + Send + Sync + 'static>(&mut self)
+    {
+        self.map_entities_fns.insert(TypeId::of::<T>(), | component: &mut BoxedComponent, map: &EntityMap |
+        {
+            component.downcast_mut::<T>().is_none_or(|component| component.map_entities(map))
+        });
+    }
+
+    /// Remaps the entity ids stored in a component or resource of type type_id,
+    /// returns false if it refers to an entity missing from the map and has to be dropped
+    pub(crate) fn map_entities(&self, type_id: TypeId, value: &mut BoxedComponent, map: &EntityMap) -> bool
+    {
+        self.map_entities_fns.get(&type_id).is_none_or(|map_entities| map_entities(value, map))
+    }
+
+    /// Registers a component that is copied by clone_entity, registering the component itself if needed
+    pub fn register_cloneable<T: Component + Clone>(&mut self)
+    {
+        <(T,)>::register_components(self);
+        self.clone_fns.insert(TypeId::of::<T>(), | registry: &mut Registry, source: usize, target: usize |
+        {
+            let component = registry.get_components::<T>()
+                .and_then(|comps| comps.get(source).ok()?.clone());
+            if let Some(component) = component
+            {
+                registry.insert_bundle(target, (component,));
+            }
+        });
+    }
+
+    /// Spawns a copy of an entity with a clone of every cloneable component, returns the new id.
+    /// Components that weren't registered with register_cloneable are skipped.
+    pub fn clone_entity(&mut self, id: usize) -> Option<usize>
+    {
+        let mut type_ids: Vec<TypeId> = match self.entities.get(id)
+        {
+            Some(entity) if entity.is_active => entity.type_ids.iter().copied().collect(),
+            _ => return None
+        };
+        type_ids.retain(|type_id| self.clone_fns.contains_key(type_id));
+
+        let copy = self.create_entity().build();
+        for type_id in type_ids
+        {
+            let clone_fn = self.clone_fns[&type_id];
+            clone_fn(self, id, copy);
+        }
+        Some(copy)
+    }
+
+    /// Spawns an entity from a prefab, using the components in overrides instead of recorded ones of the same type
+    pub fn spawn_prefab_with<B: Bundle>(&mut self, prefab: &Prefab, overrides: B) -> usize
+    {
+        let id = self.create_entity().build();
+        prefab.insert_components(self, id, &B::type_ids());
+        self.insert_bundle(id, overrides);
+        id
+    }
+
+    /// Creates a new EntityBuilder instance
+    pub fn create_entity(&mut self) -> EntityBuilder<'_>
+    {
+        EntityBuilder::new(self)
+    }
+
+    /// Spawns a new entity with every component in the bundle, returns the id
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> usize
+    {
+        self.create_entity().with_bundle(bundle).build()
+    }
+
+    /// Reserves an entity id through a shared reference, the entity is activated by flush_reserved_entities
+    pub fn reserve_entity(&self) -> usize
+    {
+        self.entities.reserve()
+    }
+
+    /// Activates every reserved entity and gives it an empty slot in every component store
+    pub fn flush_reserved_entities(&mut self)
+    {
+        if !self.entities.has_reserved()
+        {
+            return;
+        }
+
+        let first_new = self.entities.activated_size();
+        let ids = self.entities.flush_reserved();
+        for &id in ids.iter().filter(|&&id| id < first_new)
+        {
+            self.stores_mut().for_each(|comps| comps.set_none(id));
+        }
+
+        let len = self.entities.activated_size();
+        self.stores_mut().for_each(|comps| comps.resize_to_nones(len));
+    }
+
+    /// Removes every component of an entity and deactivates it, returns false if it wasn't active
+    pub fn despawn(&mut self, id: usize) -> bool
+    {
+        self.flush_reserved_entities();
+
+        let type_ids: Vec<TypeId> = match self.entities.get(id)
+        {
+            Some(entity) if entity.is_active => entity.type_ids.iter().copied().collect(),
+            _ => return false
+        };
+
+        for type_id in type_ids
+        {
+            if let Some(on_remove) = self.component_fns.get(&type_id).map(|fns| fns.on_remove)
+            {
+                on_remove(self, id);
+            }
+        }
+
+        self.stores_mut().for_each(|comps| ComponentStore::drop(comps, id));
+        self.entities.drop(id);
+
+        // apply the cleanup policy of every relation that pointed at this entity
+        for source in self.relation_sources.remove(&id).unwrap_or_default()
+        {
+            match source.cleanup
+            {
+                RelationCleanup::RemoveRelation => (source.remove)(self, source.id),
+                RelationCleanup::DespawnSource => { self.despawn(source.id); }
+            }
+        }
+        true
+    }
+
+    /// Activates count empty entities, growing every component store once, returns the ids
+    pub fn reserve_entities(&mut self, count: usize) -> Vec<usize>
+    {
+        self.flush_reserved_entities();
+
+        let reused = self.entities.deactivated_size().min(count);
+        let ids = self.entities.activate_many(count);
+
+        // reused ids come first, clear any data left in their slots
+        for &id in &ids[..reused]
+        {
+            self.stores_mut().for_each(|comps| comps.set_none(id));
+        }
+
+        let len = self.entities.activated_size();
+        self.stores_mut().for_each(|comps| comps.resize_to_nones(len));
+
+        ids
+    }
+
+    /// Spawns an entity for every bundle, allocating ids and storage once, returns the ids
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, bundles: I) -> Vec<usize>
+    {
+        let bundles: Vec<B> = bundles.into_iter().collect();
+        B::register_components(self);
+        let ids = self.reserve_entities(bundles.len());
+
+        let type_ids = B::type_ids();
+        for (&id, bundle) in ids.iter().zip(bundles)
+        {
+            bundle.insert_components(self, id);
+            // on_add hooks may have added components of their own
+            if let Some(entity) = self.entities.get_mut(id)
+            {
+                entity.type_ids.extend(type_ids.iter().copied());
+            }
+        }
+
+        ids
+    }
+
+    /// Adds every component in the bundle to an existing entity
+    pub fn insert_bundle<B: Bundle>(&mut self, id: usize, bundle: B)
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return;
+        }
+
+        B::register_components(self);
+        bundle.insert_components(self, id);
+        if let Some(entity) = self.entities.get_mut(id)
+        {
+            entity.type_ids.extend(B::type_ids());
+        }
+    }
+
+    /// Removes every component in the bundle from an entity, returns the bundle if all were present
+    pub fn remove_bundle<B: Bundle>(&mut self, id: usize) -> Option<B>
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return None;
+        }
+
+        // a bundle that doesn't fully match removes nothing
+        let type_ids = B::type_ids();
+        if !type_ids.iter().all(|type_id| self.components.get(type_id).is_some_and(|store| store.get_ptr(id).is_some()))
+        {
+            return None;
+        }
+
+        let bundle = B::take_components(self, id);
+        if let Some(entity) = self.entities.get_mut(id)
+        {
+            for type_id in type_ids
+            {
+                entity.type_ids.remove(&type_id);
+            }
+        }
+        bundle
+    }
+
+    /// Returns the parent of an entity if it has one
+    pub fn parent(&self, id: usize) -> Option<usize>
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return None;
+        }
+
+        let parents = self.get_components::<Parent>()?;
+        let parent = parents.get(id).ok()?;
+        parent.as_ref().map(Parent::id)
+    }
+
+    /// Returns the children of an entity in the order they were added
+    pub fn children(&self, id: usize) -> Vec<usize>
+    {
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return Vec::new();
+        }
+
+        self.get_components::<Children>()
+            .and_then(|children| children.get(id).ok()?.as_ref().map(|c| c.ids().to_vec()))
+            .unwrap_or_default()
+    }
+
+    /// Makes parent the parent of child, detaching child from its previous parent.
+    /// Fails if either entity isn't active or if parent is child or one of its descendants.
+    pub fn set_parent(&mut self, child: usize, parent: usize) -> Result<(), HierarchyError>
+    {
+        self.flush_reserved_entities();
+
+        for id in [child, parent]
+        {
+            if !self.entities.get(id).is_some_and(|e| e.is_active)
+            {
+                return Err(HierarchyError { message: format!("Failed to set parent of entity {} to {}: entity {} is not active", self.label(child), self.label(parent), self.label(id)) });
+            }
+        }
+
+        // walk up from the new parent, finding child there would close a cycle
+        let mut ancestor = Some(parent);
+        while let Some(id) = ancestor
+        {
+            if id == child
+            {
+                let (child, parent) = (self.label(child), self.label(parent));
+                return Err(HierarchyError { message: format!("Failed to set parent of entity {child} to {parent}: entity {parent} is a descendant of {child}") });
+            }
+            ancestor = self.parent(id);
+        }
+
+        if self.parent(child) == Some(parent)
+        {
+            return Ok(());
+        }
+
+        // replacing Parent detaches child from the old parent, its on_add hook appends it to the new one
+        self.insert_bundle(child, (Parent(parent),));
+        Ok(())
+    }
+
+    /// Detaches an entity from its parent, returns the old parent if it had one
+    pub fn remove_parent(&mut self, child: usize) -> Option<usize>
+    {
+        self.flush_reserved_entities();
+        self.remove_bundle::<(Parent,)>(child).map(|(parent,)| parent.id())
+    }
+
+    /// Returns every descendant of an entity, depth first with parents before their children
+    pub fn descendants(&self, id: usize) -> Vec<usize>
+    {
+        let mut ids = Vec::new();
+        let mut stack: Vec<usize> = self.children(id).into_iter().rev().collect();
+        while let Some(id) = stack.pop()
+        {
+            ids.push(id);
+            stack.extend(self.children(id).into_iter().rev());
+        }
+        ids
+    }
+
+    /// Returns every active entity ordered so parents always come before their children.
+    /// Roots are in id order, each followed by its descendants depth first.
+    pub fn hierarchy_order(&self) -> Vec<usize>
+    {
+        let mut ids = Vec::new();
+        let roots: Vec<usize> = self.entities.active.iter()
+            .filter(|e| e.is_active && self.parent(e.id).is_none())
+            .map(|e| e.id)
+            .collect();
+        for root in roots
+        {
+            ids.push(root);
+            ids.extend(self.descendants(root));
+        }
+        ids
+    }
+
+    /// Despawns an entity and all of its descendants, returns false if it wasn't active
+    pub fn despawn_recursive(&mut self, id: usize) -> bool
+    {
+        self.flush_reserved_entities();
+        if !self.entities.get(id).is_some_and(|e| e.is_active)
+        {
+            return false;
+        }
+
+        let mut ids = vec![id];
+        ids.extend(self.descendants(id));
+        // children first, so each despawn only detaches a leaf
+        for id in ids.into_iter().rev()
+        {
+            self.despawn(id);
+        }
+        true
+    }
+
+    /// Moves entities and all of their descendants out of other into this registry, returns the new id of every moved entity.
+    /// Entity ids stored in components registered with register_map_entities are remapped,
+    /// components that can't be remapped because they refer to an entity that wasn't moved are dropped.
+    /// Runtime defined components are moved to the one registered here with the same name, layout and drop fn.
+    pub fn move_entities(&mut self, other: &mut Registry, ids: &[usize]) -> EntityMap
+    {
+        self.flush_reserved_entities();
+        other.flush_reserved_entities();
+
+        let mut moved = Vec::new();
+        let mut seen = HashSet::new();
+        for &id in ids
+        {
+            if !other.entities.get(id).is_some_and(|e| e.is_active) || seen.contains(&id)
+            {
+                continue;
+            }
+
+            for id in std::iter::once(id).chain(other.descendants(id))
+            {
+                if seen.insert(id)
+                {
+                    moved.push(id);
+                }
+            }
+        }
+
+        // moved roots leave their parents behind
+        for &id in &moved
+        {
+            if other.parent(id).is_some_and(|parent| !seen.contains(&parent))
+            {
+                other.remove_parent(id);
+            }
+        }
+
+        let mut map = EntityMap::new();
+        let new_ids = self.reserve_entities(moved.len());
+        for (&old, &new) in moved.iter().zip(&new_ids)
+        {
+            map.insert(old, new);
+        }
+
+        let taken = other.take_entities(&moved);
+        for (entity, id) in taken.into_iter().zip(new_ids)
+        {
+            for (descriptor, slot) in entity.dynamic
+            {
+                let component = self.dynamic_component_id(&descriptor);
+                if let Some(comps) = self.dynamic_components.get_mut(&component)
+                {
+                    // both stores were created from descriptors with the same layout
+                    unsafe { comps.put(id, slot) };
+                }
+            }
+
+            for (type_id, mut component) in entity.components
+            {
+                let mapper = if self.map_entities_fns.contains_key(&type_id) { &*self } else { &*other };
+                if !mapper.map_entities(type_id, &mut component, &map)
+                {
+                    continue;
+                }
+                if let Some(fns) = other.component_fns.get(&type_id)
+                {
+                    (fns.insert)(self, id, component);
+                }
+            }
+        }
+        map
+    }
+
+    /// Moves every entity out of other into this registry, returns the new id of every moved entity
+    pub fn merge_from(&mut self, other: &mut Registry) -> EntityMap
+    {
+        other.flush_reserved_entities();
+        let ids: Vec<usize> = other.entities.active.iter().filter(|e| e.is_active).map(|e| e.id).collect();
+        self.move_entities(other, &ids)
+    }
+
+    /// Takes every component of each entity out of the stores and deactivates it, without running on_remove hooks.
+    /// Hooks would tear down the hierarchy and relations being moved, so the indexes are cleaned up here instead.
+    fn take_entities(&mut self, ids: &[usize]) -> Vec<TakenEntity>
+    {
+        let mut taken = Vec::with_capacity(ids.len());
+        for &id in ids
+        {
+            let type_ids: Vec<TypeId> = self.entities.get(id).map(|e| e.type_ids.iter().copied().collect()).unwrap_or_default();
+            let components = type_ids.into_iter()
+                .filter_map(|type_id| Some((type_id, self.components.get_mut(&type_id)?.take_boxed(id)?)))
+                .collect();
+            let dynamic = self.dynamic_components.values_mut()
+                .filter_map(|comps| Some((comps.descriptor().clone(), comps.take(id)?)))
+                .collect();
+            taken.push(TakenEntity { components, dynamic });
+
+            self.unindex_name(id);
+            self.stores_mut().for_each(|comps| ComponentStore::drop(comps, id));
+            self.entities.drop(id);
+        }
+
+        // relations from moved sources are no longer in this registry
+        let moved: HashSet<usize> = ids.iter().copied().collect();
+        self.relation_sources.retain(|_, sources|
+        {
+            sources.retain(|s| !moved.contains(&s.id));
+            !sources.is_empty()
+        });
+
+        // relations left behind that pointed at a moved entity lost their target
+        for &id in ids
+        {
+            for source in self.relation_sources.remove(&id).unwrap_or_default()
+            {
+                match source.cleanup
+                {
+                    RelationCleanup::RemoveRelation => (source.remove)(self, source.id),
+                    RelationCleanup::DespawnSource => { self.despawn(source.id); }
+                }
+            }
+        }
+        taken
+    }
+
+    /// Appends an entity to its parent's Children unless it's listed already, called after its Parent is added
+    pub(crate) fn attach_to_parent(&mut self, id: usize)
+    {
+        // a parent that isn't active has no Children to keep in sync
+        let Some(parent) = self.parent(id).filter(|&parent| self.entities.get(parent).is_some_and(|e| e.is_active)) else
+        {
+            return;
+        };
+
+        let listed = self.get_components_mut::<Children>()
+            .and_then(|children| children.get_mut(parent).ok()?.as_mut().map(|c|
+            {
+                if !c.0.contains(&id)
+                {
+                    c.0.push(id);
+                }
+            }))
+            .is_some();
+        if !listed
+        {
+            self.insert_bundle(parent, (Children(vec![id]),));
+        }
+    }
+
+    /// Removes an entity from its parent's Children, called before its Parent is removed
+    pub(crate) fn detach_from_parent(&mut self, id: usize)
+    {
+        let Some(parent) = self.parent(id) else
+        {
+            return;
+        };
+
+        let is_empty = self.get_components_mut::<Children>()
+            .and_then(|children| children.get_mut(parent).ok()?.as_mut().map(|c|
+            {
+                c.0.retain(|&child| child != id);
+                c.0.is_empty()
+            }))
+            .unwrap_or(false);
+        if is_empty
+        {
+            self.remove_bundle::<(Children,)>(parent);
+        }
+    }
+
+    /// Clears the Parent of every child of an entity, called before its Children are removed
+    pub(crate) fn orphan_children(&mut self, id: usize)
+    {
+        for child in self.children(id)
+        {
+            if self.parent(child) != Some(id)
+            {
+                continue;
+            }
+
+            // cleared directly, the Parent hook would edit the Children being removed
+            if let Some(parents) = self.get_components_mut::<Parent>()
+            {
+                if let Ok(mut parent) = parents.get_mut(child)
+                {
+                    *parent = None;
+                }
+            }
+            if let Some(entity) = self.entities.get_mut(child)
+            {
+                entity.type_ids.remove(&TypeId::of::<Parent>());
+            }
+        }
+    }
 
-use crate::{
-    component_store::{ComponentStore, VecStore}, 
-    entity::{Entity, EntityManager}, 
-    entity_builder::EntityBuilder, query::QueryBuilder, 
-    // query::QueryBuilder
-};
+    /// Links source to target through the relation, replacing any relation of type R source had.
+    /// Fails if either entity isn't active.
+    pub fn add_relation<R: Relation>(&mut self, source: usize, relation: R, target: usize) -> Result<(), RelationError>
+    {
+        self.flush_reserved_entities();
 
-pub struct Registry
-{
-    pub components: HashMap<TypeId, Box<dyn ComponentStore>>,
-    pub(crate) entities: EntityManager
-}
+        for id in [source, target]
+        {
+            if !self.entities.get(id).is_some_and(|e| e.is_active)
+            {
+                return Err(RelationError { message: format!("Failed to relate entity {} to {}: entity {} is not active", self.label(source), self.label(target), self.label(id)) });
+            }
+        }
 
-impl Registry
-{
-    /// Creates a new registry
-    pub fn new() -> Self
+        self.insert_bundle(source, (Related::new(relation, target),));
+        Ok(())
+    }
+
+    /// Returns the sources of every relation of type R pointing at target, in the order they were added
+    pub fn sources_of<R: Relation>(&self, target: usize) -> Vec<usize>
     {
-        Self {
-            components: HashMap::new(),
-            entities: EntityManager::new()
+        let relation = TypeId::of::<R>();
+        self.relation_sources.get(&target)
+            .map(|sources| sources.iter().filter(|s| s.relation == relation).map(|s| s.id).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the target of the relation of type R on source if it has one
+    pub fn target_of<R: Relation>(&self, source: usize) -> Option<usize>
+    {
+        let related = self.get_components::<Related<R>>()?;
+        let related = related.get(source).ok()?;
+        related.as_ref().map(Related::target)
+    }
+
+    /// Records that source points at target through the relation type, called when a Related component is added
+    pub(crate) fn index_relation(&mut self, relation: TypeId, source: usize, target: usize, cleanup: RelationCleanup, remove: fn(&mut Registry, usize))
+    {
+        self.relation_sources.entry(target).or_default().push(RelationSource { relation, id: source, cleanup, remove });
+    }
+
+    /// Forgets that source points at target through the relation type, called before a Related component is removed
+    pub(crate) fn unindex_relation(&mut self, relation: TypeId, source: usize, target: usize)
+    {
+        if let Some(sources) = self.relation_sources.get_mut(&target)
+        {
+            sources.retain(|s| s.relation != relation || s.id != source);
+            if sources.is_empty()
+            {
+                self.relation_sources.remove(&target);
+            }
         }
     }
 
-    /// Adds a new component to the registry
-    pub fn register_component<T: Any + Send + Sync>(&mut self)
+    /// Names an entity, replacing its old Name and keeping the name index up to date.
+    /// This is the only way to rename an entity, Names can't be mutated in place.
+    pub fn set_name(&mut self, id: usize, name: impl Into<Name>)
     {
-        let type_id = TypeId::of::<T>();
-        let mut comps: VecStore<T> = VecStore::new();
-        // if entities already exist, populate the Vec with None
-        if !self.entities.is_activated_empty()
+        self.insert_bundle(id, (name.into(),));
+    }
+
+    /// Returns the first entity named name
+    pub fn find_by_name(&self, name: &str) -> Option<usize>
+    {
+        self.names.get(name)?.first().copied()
+    }
+
+    /// Returns every entity named name, in the order they were named
+    pub fn find_all_by_name(&self, name: &str) -> Vec<usize>
+    {
+        self.names.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the id of an entity followed by its name if it has one, for messages
+    pub fn label(&self, id: usize) -> String
+    {
+        self.entities.get(id).map(Entity::label).unwrap_or_else(|| id.to_string())
+    }
+
+    /// Indexes the Name stored at id and copies it to the entity, called when a Name is added
+    pub(crate) fn index_name(&mut self, id: usize)
+    {
+        let Some(name) = self.get_components::<Name>().and_then(|names| names.get(id).ok()?.as_ref().map(|n| n.as_str().to_owned())) else
         {
-            comps.resize_to_nones(self.entities.activated_size());
+            return;
+        };
+
+        self.names.entry(name.clone()).or_default().push(id);
+        if let Some(entity) = self.entities.get_mut(id)
+        {
+            entity.name = Some(name);
         }
+    }
 
-        // let b_comps: Box<dyn ComponentStore + Send + Sync> = Box::new(comps);
-        self.components.insert(type_id, Box::new(comps));
+    /// Forgets the Name stored at id, called before a Name is removed
+    pub(crate) fn unindex_name(&mut self, id: usize)
+    {
+        let Some(name) = self.entities.get_mut(id).and_then(|entity| entity.name.take()) else
+        {
+            return;
+        };
+
+        if let Some(ids) = self.names.get_mut(&name)
+        {
+            ids.retain(|&named| named != id);
+            if ids.is_empty()
+            {
+                self.names.remove(&name);
+            }
+        }
     }
 
-    /// Creates a new EntityBuilder instance
-    pub fn create_entity(&mut self) -> EntityBuilder
+    /// Returns true if the entity id has a component of type T stored
+    pub fn has_component<T: Component>(&self, id: usize) -> bool
     {
-        EntityBuilder::new(self)
+        self.get_components::<T>().is_some_and(|comps| comps.get(id).is_ok_and(|component| component.is_some()))
     }
 
-    /// Revtrieves a Vecstore of Type T components if they exist
-    pub fn get_components<T: Any>(&self) -> Option<&VecStore<T>>
+    /// Sets the component of type T at the entity id, registering it if needed.
+    /// Runs the on_remove hook for a replaced component, then the on_add hook.
+    pub(crate) fn set_component<T: Component>(&mut self, id: usize, data: T)
     {
-        let type_id = TypeId::of::<T>();
-        if let Some(comps) = self.components.get(&type_id)
+        if !self.components.contains_key(&TypeId::of::<T>())
+        {
+            self.register_component::<T>();
+        }
+
+        // replacing a component marks it changed, otherwise it is added
+        let replaced = self.has_component::<T>(id);
+        if replaced
+        {
+            T::on_remove(self, id);
+        }
+
+        if let Some(comps) = self.get_components_mut::<T>()
+        {
+            comps.insert(id, data);
+        }
+
+        T::on_add(self, id);
+    }
+
+    /// Takes the component of type T out of the store at the entity id, runs the on_remove hook first
+    pub(crate) fn take_component<T: Component>(&mut self, id: usize) -> Option<T>
+    {
+        if !self.has_component::<T>(id)
         {
-            return comps.as_any().downcast_ref::<VecStore<T>>();
-        } else {
             return None;
         }
+
+        T::on_remove(self, id);
+        let component = self.get_components_mut::<T>()?.take_boxed(id)?;
+        component.downcast::<T>().ok().map(|component| *component)
     }
 
-    /// Revtrieves a mutable Vecstore of Type T components if they exist
-    pub fn get_components_mut<T: Any>(&mut self) -> Option<&mut VecStore<T>>
+    /// Returns the change ticks of the component of type T at the entity id if it has one
+    pub fn component_ticks<T: Component>(&self, id: usize) -> Option<ComponentTicks>
     {
-        let type_id = TypeId::of::<T>();
-        if let Some(comps) = self.components.get_mut(&type_id)
+        if !self.has_component::<T>(id)
         {
-            return comps.as_any_mut().downcast_mut::<VecStore<T>>();
-        } else {
             return None;
         }
+        self.get_components::<T>()?.ticks(id)
+    }
+
+    /// Returns true if the component of type T was added to the entity since the last change tick
+    pub fn is_component_added<T: Component>(&self, id: usize) -> bool
+    {
+        self.component_ticks::<T>(id).is_some_and(|ticks| ticks.is_added(self.last_change_tick))
+    }
+
+    /// Returns true if the component of type T was added or mutably accessed since the last change tick
+    pub fn is_component_changed<T: Component>(&self, id: usize) -> bool
+    {
+        self.component_ticks::<T>(id).is_some_and(|ticks| ticks.is_changed(self.last_change_tick))
+    }
+
+    /// Replaces every entity and clears every component store, dynamic ones included, used when restoring a snapshot
+    #[cfg(feature = "serde")]
+    pub(crate) fn reset_entities(&mut self, entities: EntityManager)
+    {
+        self.entities = entities;
+        self.relation_sources.clear();
+        self.names.clear();
+
+        let len = self.entities.activated_size();
+        for comps in self.stores_mut()
+        {
+            comps.resize_to_nones(0);
+            comps.resize_to_nones(len);
+        }
+    }
+
+    /// Activates the spawned ids, grows to slots entities and replaces the free list, used when applying a delta
+    #[cfg(feature = "serde")]
+    pub(crate) fn apply_allocator(&mut self, slots: usize, dropped: Vec<usize>, spawned: &[usize])
+    {
+        self.flush_reserved_entities();
+        self.entities.apply_allocator(slots, dropped, spawned);
+
+        let len = self.entities.activated_size();
+        for comps in self.stores_mut()
+        {
+            comps.resize_to_nones(len);
+            spawned.iter().filter(|&&id| id < len).for_each(|&id| comps.set_none(id));
+        }
+    }
+
+    /// Sets the tick added and mutated components are marked with
+    pub(crate) fn set_change_tick(&mut self, tick: u64)
+    {
+        self.change_tick = tick;
+        self.stores_mut().for_each(|comps| comps.set_change_tick(tick));
+    }
+
+    /// Revtrieves the store of Type T components if it exists
+    pub fn get_components<T: Component>(&self) -> Option<&T::Storage>
+    {
+        let type_id = TypeId::of::<T>();
+        self.components.get(&type_id)?.as_any().downcast_ref::<T::Storage>()
+    }
+
+    /// Revtrieves the mutable store of Type T components if it exists
+    pub fn get_components_mut<T: Component>(&mut self) -> Option<&mut T::Storage>
+    {
+        let type_id = TypeId::of::<T>();
+        self.components.get_mut(&type_id)?.as_any_mut().downcast_mut::<T::Storage>()
     }
 
     /// Returns an entity given an id if it exists
@@ -89,25 +1129,119 @@ impl Registry
     /// Returns a vector of entity ids given a set of components TypeIds
     pub fn get_entity_ids(&self, type_ids: &HashSet<TypeId>) -> Vec<usize>
     {
-        self.entities.filter_by_components(&type_ids)
+        self.entities.filter_by_components(type_ids)
     }
 
     /// Starts QueryBuilder
-    pub fn query(&self) -> QueryBuilder
+    pub fn query(&self) -> QueryBuilder<'_>
     {
         QueryBuilder::new(self)
     }
 
+    /// Calls f with the id and the item of every entity matching Q, such as `(&Position, Option<&mut Velocity>)`.
+    /// Mutably fetched components are marked changed once mutably dereferenced. Panics if Q writes a component it also reads or writes elsewhere.
+    pub fn query_each<Q: QueryData>(&mut self, f: impl FnMut(usize, Q::Item<'_>))
+    {
+        self.query_filtered::<Q, ()>(f);
+    }
+
+    /// Like query_each, only for entities passing the filter F, such as `(With<Health>, Or<(With<Enemy>, With<Neutral>)>)`
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self, mut f: impl FnMut(usize, Q::Item<'_>))
+    {
+        let (mut reads, mut writes) = (Vec::new(), Vec::new());
+        Q::add_access(&mut reads, &mut writes);
+        for (index, write) in writes.iter().enumerate()
+        {
+            if reads.contains(write) || writes[index + 1..].contains(write)
+            {
+                panic!("Query {} accesses a component mutably more than once", std::any::type_name::<Q>());
+            }
+        }
+
+        let mut types = HashSet::new();
+        Q::add_required(&mut types);
+        let mut ids = self.get_entity_ids(&types);
+        ids.retain(|&id| Q::matches(self, id) && F::matches(self, id));
+
+        // only components the closure mutably dereferenced are marked changed
+        let mut changes = Vec::new();
+        for &id in &ids
+        {
+            if let Some(mut fetch) = Q::fetch(self, id)
+            {
+                f(id, Q::item(&mut fetch));
+                Q::written(&fetch, id, &mut changes);
+            }
+        }
+
+        for (id, mark_changed) in changes
+        {
+            mark_changed(self, id);
+        }
+    }
+
+}
+
+/// A component value with its type erased
+type BoxedComponent = Box<dyn Any + Send + Sync>;
+
+/// Operations on a registered component type that don't need to know the type
+struct ComponentFns
+{
+    on_remove: fn(&mut Registry, usize), // Component::on_remove
+    insert: fn(&mut Registry, usize, BoxedComponent), // inserts a boxed component with insert_bundle
+    insert_raw: unsafe fn(&mut Registry, usize, *const u8), // reads a component from a pointer and inserts it with insert_bundle
+    remove: fn(&mut Registry, usize) -> bool // drops a component with remove_bundle
+}
+
+type ReflectCallback<'a> = &'a mut dyn FnMut(&dyn Reflect);
+type ReflectMutCallback<'a> = &'a mut dyn FnMut(&mut dyn Reflect);
+
+/// Calls a callback with a component as a Reflect, without knowing its type
+struct ReflectFns
+{
+    get: fn(&Registry, usize, ReflectCallback),
+    get_mut: fn(&mut Registry, usize, ReflectMutCallback)
+}
+
+/// A relation pointing at a target entity, kept by the target so it can be cleaned up on despawn
+struct RelationSource
+{
+    relation: TypeId,
+    id: usize, // source entity
+    cleanup: RelationCleanup,
+    remove: fn(&mut Registry, usize) // removes the Related component from the source
+}
+
+/// The components take_entities took out of one entity
+struct TakenEntity
+{
+    components: Vec<(TypeId, BoxedComponent)>,
+    dynamic: Vec<(ComponentDescriptor, ptr::NonNull<u8>)> // allocations owned by the entity, put into another DynamicStore
+}
+
+impl Default for Registry
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests
 {
-    use crate::{component_store::ComponentError, entity};
+    use std::thread;
+
+    use proptest::prelude::*;
+
+    use crate::component_store::{SparseStore, VecStore};
+    use crate::query::{With, Without};
 
     use super::*;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn create_entity() -> anyhow::Result<()>
     {
         let mut registry = Registry::new();
@@ -170,14 +1304,324 @@ mod tests
         pub value: u32
     }
 
+    impl Component for Health
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    #[test]
+    fn bundles()
+    {
+        let mut registry = Registry::new();
+        let e1 = registry.spawn((Health{value: 10}, Speed{value: 5}));
+        let e2 = registry.create_entity()
+            .with_bundle((Health{value: 20},))
+            .build();
+
+        assert_eq!(registry.query().with_component::<Health>().get(), vec![e1, e2]);
+        assert_eq!(registry.query().with_component::<Speed>().get(), vec![e1]);
+
+        registry.insert_bundle(e2, (Speed{value: 7}, Position{X: 0.0, Y: 0.0}));
+        assert_eq!(registry.query().with_component::<Speed>().with_component::<Position>().get(), vec![e2]);
+
+        // e1 has no Position, so nothing is removed
+        assert!(registry.remove_bundle::<(Health, Speed, Position)>(e1).is_none());
+        assert_eq!(registry.get_entity(e1).unwrap().type_ids, HashSet::from([TypeId::of::<Health>(), TypeId::of::<Speed>()]));
+        assert_eq!(registry.get_components::<Health>().unwrap().get(e1).unwrap().as_ref().unwrap().value, 10);
+        assert!(registry.has_component::<Speed>(e1));
+
+        let (health, speed) = registry.remove_bundle::<(Health, Speed)>(e2).unwrap();
+        assert_eq!((health.value, speed.value), (20, 7));
+        assert_eq!(registry.get_entity(e2).unwrap().type_ids, HashSet::from([TypeId::of::<Position>()]));
+    }
+
+    #[test]
+    fn spawn_batch()
+    {
+        let mut registry = Registry::new();
+        registry.register_component::<Speed>();
+        let ids = registry.spawn_batch((0..4).map(|value| (Health{value}, Position{X: 0.0, Y: 0.0})));
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert_eq!(registry.query().with_component::<Health>().with_component::<Position>().get(), ids);
+
+        // reused ids are cleared, new ids extend every store exactly once
+        registry.entities.drop(1);
+        let ids = registry.spawn_batch((10..12).map(|value| (Speed{value},)));
+        assert_eq!(ids, vec![1, 4]);
+        assert!(registry.get_components::<Health>().unwrap().get(1).unwrap().is_none());
+        assert_eq!(registry.get_components::<Health>().unwrap().len(), 5);
+        assert_eq!(registry.get_components::<Speed>().unwrap().len(), 5);
+        assert_eq!(registry.query().with_component::<Speed>().get(), ids);
+
+        let reserved = registry.reserve_entities(3);
+        assert_eq!(reserved, vec![5, 6, 7]);
+        assert!(registry.get_entity(6).unwrap().type_ids.is_empty());
+        assert_eq!(registry.get_components::<Position>().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn reuse_last_dropped_id()
+    {
+        let mut registry = Registry::new();
+        let e1 = registry.create_entity().with_component(Health{value: 1}).build();
+        let e2 = registry.create_entity().with_component(Speed{value: 2}).build();
+        registry.despawn(e1);
+
+        // the only dropped id is reused, its slot is cleared instead of appending to every store
+        let e3 = registry.create_entity().with_component(Speed{value: 3}).build();
+        assert_eq!(e3, e1);
+        assert_eq!(registry.get_components::<Health>().unwrap().len(), 2);
+        assert_eq!(registry.get_components::<Speed>().unwrap().len(), 2);
+        assert!(registry.get_components::<Health>().unwrap().get(e3).unwrap().is_none());
+        assert_eq!(registry.get_components::<Speed>().unwrap().get(e2).unwrap().as_ref().unwrap().value, 2);
+        assert_eq!(registry.query().with_component::<Speed>().get(), vec![e1, e2]);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op
+    {
+        Spawn(u32),
+        SpawnWithSpeed(u32),
+        SpawnBatch(Vec<u32>),
+        Despawn(usize) // index into the live entities
+    }
+
+    fn op() -> impl Strategy<Value = Op>
+    {
+        prop_oneof![
+            any::<u32>().prop_map(Op::Spawn),
+            any::<u32>().prop_map(Op::SpawnWithSpeed),
+            prop::collection::vec(any::<u32>(), 0..4).prop_map(Op::SpawnBatch),
+            any::<usize>().prop_map(Op::Despawn),
+        ]
+    }
+
+    proptest!
+    {
+        #[test]
+        fn spawn_despawn_keeps_stores_consistent(ops in prop::collection::vec(op(), 1..64))
+        {
+            let mut registry = Registry::new();
+            registry.register_component::<Health>();
+            registry.register_component::<Speed>();
+            // entity id -> (health, has speed)
+            let mut live: HashMap<usize, (u32, bool)> = HashMap::new();
+
+            for op in ops
+            {
+                match op
+                {
+                    Op::Spawn(value) =>
+                    {
+                        let id = registry.create_entity().with_component(Health{value}).build();
+                        prop_assert!(live.insert(id, (value, false)).is_none());
+                    },
+                    Op::SpawnWithSpeed(value) =>
+                    {
+                        let id = registry.spawn((Health{value}, Speed{value}));
+                        prop_assert!(live.insert(id, (value, true)).is_none());
+                    },
+                    Op::SpawnBatch(values) =>
+                    {
+                        let ids = registry.spawn_batch(values.iter().map(|&value| (Health{value},)));
+                        for (id, value) in ids.into_iter().zip(values)
+                        {
+                            prop_assert!(live.insert(id, (value, false)).is_none());
+                        }
+                    },
+                    Op::Despawn(index) =>
+                    {
+                        let mut ids: Vec<usize> = live.keys().copied().collect();
+                        ids.sort();
+                        if !ids.is_empty()
+                        {
+                            let id = ids[index % ids.len()];
+                            prop_assert!(registry.despawn(id));
+                            live.remove(&id);
+                        }
+                    }
+                }
+
+                let len = registry.entities.activated_size();
+                let healths = registry.get_components::<Health>().unwrap();
+                let speeds = registry.get_components::<Speed>().unwrap();
+                prop_assert_eq!(healths.len(), len);
+                prop_assert_eq!(speeds.len(), len);
+
+                for id in 0..len
+                {
+                    let health = healths.get(id).unwrap().as_ref().map(|h| h.value);
+                    let speed = speeds.get(id).unwrap().as_ref().map(|s| s.value);
+                    match live.get(&id)
+                    {
+                        Some(&(value, has_speed)) =>
+                        {
+                            prop_assert_eq!(health, Some(value));
+                            prop_assert_eq!(speed, has_speed.then_some(value));
+                        },
+                        None =>
+                        {
+                            prop_assert_eq!(health, None);
+                            prop_assert_eq!(speed, None);
+                        }
+                    }
+                }
+
+                let mut expected: Vec<usize> = live.keys().copied().collect();
+                expected.sort();
+                prop_assert_eq!(registry.query().with_component::<Health>().get(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn component_hooks()
+    {
+        let mut registry = Registry::new();
+        let e1 = registry.spawn((Tracked,));
+        let e2 = registry.create_entity().with_component(Tracked).build();
+        assert_eq!(counter(&registry), 2);
+
+        // replacing a component runs on_remove for the old value, then on_add
+        registry.insert_bundle(e1, (Tracked,));
+        assert_eq!(counter(&registry), 2);
+
+        registry.remove_bundle::<(Tracked,)>(e2);
+        assert_eq!(counter(&registry), 1);
+        // removing a missing component doesn't run the hook
+        registry.remove_bundle::<(Tracked,)>(e2);
+        assert_eq!(counter(&registry), 1);
+    }
+
+    #[test]
+    fn sparse_get_mut_missing()
+    {
+        let mut registry = Registry::new();
+        let e1 = registry.spawn((Tracked,));
+        let e2 = registry.spawn((Health{value: 1},));
+
+        let store = registry.get_components_mut::<Tracked>().unwrap();
+        assert!(store.get_mut(e1).is_ok());
+        // entities without the component don't get an empty slot
+        assert!(store.get_mut(e2).is_err());
+        assert_eq!(store.len(), 1);
+        assert!(!registry.has_component::<Tracked>(e2));
+    }
+
+    #[test]
+    fn out_of_range_ids()
+    {
+        let mut registry = Registry::new();
+        let e1 = registry.spawn((Health{value: 1},));
+        let e2 = registry.spawn((Health{value: 2},));
+        registry.despawn(e2);
+
+        for id in [e2, 1000]
+        {
+            assert!(!registry.has_component::<Health>(id));
+            assert!(!registry.is_component_added::<Health>(id));
+            assert!(!registry.is_component_changed::<Health>(id));
+            assert_eq!(registry.component_ticks::<Health>(id), None);
+            assert!(!With::<Health>::matches(&registry, id));
+            assert!(Without::<Health>::matches(&registry, id));
+        }
+        let store = registry.get_components_mut::<Health>().unwrap();
+        assert!(store.get(1000).is_err());
+        assert!(store.get_mut(1000).is_err());
+        assert!(registry.has_component::<Health>(e1));
+    }
+
+    /// Gives its entity a Speed when added
+    struct Armed;
+
+    impl Component for Armed
+    {
+        type Storage = VecStore<Self>;
+
+        fn on_add(registry: &mut Registry, id: usize)
+        {
+            registry.insert_bundle(id, (Speed{value: 1},));
+        }
+    }
+
+    #[test]
+    fn builder_keeps_hook_components()
+    {
+        let mut registry = Registry::new();
+        let id = registry.create_entity().with_component(Armed).build();
+        assert!(registry.get_entity(id).unwrap().type_ids.contains(&TypeId::of::<Speed>()));
+
+        // despawning removes the hook's component too
+        registry.despawn(id);
+        assert!(!registry.has_component::<Speed>(id));
+    }
+
+    #[test]
+    fn spawn_batch_keeps_hook_components()
+    {
+        let mut registry = Registry::new();
+        let ids = registry.spawn_batch(vec![(Armed,), (Armed,)]);
+        for id in ids
+        {
+            assert!(registry.get_entity(id).unwrap().type_ids.contains(&TypeId::of::<Speed>()));
+            registry.despawn(id);
+            assert!(!registry.has_component::<Speed>(id));
+        }
+    }
+
+    #[allow(dead_code)]
     struct Speed
     {
         pub value: u32
     }
 
+    impl Component for Speed
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    #[allow(dead_code, non_snake_case)]
     struct Position
     {
         pub X: f32,
         pub Y: f32
     }
+
+    impl Component for Position
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    /// Counts how many Tracked components are alive, through its hooks
+    struct Tracked;
+
+    impl Component for Tracked
+    {
+        type Storage = SparseStore<Self>;
+
+        fn on_add(registry: &mut Registry, _id: usize)
+        {
+            registry.set_component(0, Counter{value: counter(registry) + 1});
+        }
+
+        fn on_remove(registry: &mut Registry, _id: usize)
+        {
+            registry.set_component(0, Counter{value: counter(registry) - 1});
+        }
+    }
+
+    struct Counter
+    {
+        pub value: i32
+    }
+
+    impl Component for Counter
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    fn counter(registry: &Registry) -> i32
+    {
+        registry.get_components::<Counter>().and_then(|comps| comps.get(0).ok()?.as_ref().map(|c| c.value)).unwrap_or(0)
+    }
 }
\ No newline at end of file
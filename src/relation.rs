@@ -0,0 +1,204 @@
+use std::{any::TypeId, fmt};
+
+use crate::{component::Component, component_store::SparseStore, entity_map::{EntityMap, MapEntities}, registry::Registry};
+
+pub struct RelationError
+{
+    pub(crate) message: String
+}
+
+impl fmt::Display for RelationError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for RelationError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// What happens to the source of a relation when its target is despawned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RelationCleanup
+{
+    /// Removes the relation from the source
+    RemoveRelation,
+    /// Despawns the source as well
+    DespawnSource
+}
+
+/// A kind of link from a source entity to a target entity, such as `Targets` or `DockedAt`.
+/// Added with World::add_relation, which stores it on the source as a Related component.
+pub trait Relation: Send + Sync + 'static
+{
+    /// Applied to every source when the target is despawned
+    const ON_TARGET_DESPAWN: RelationCleanup = RelationCleanup::RemoveRelation;
+}
+
+/// Component linking its entity to a target entity through the relation R
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Related<R: Relation>
+{
+    relation: R,
+    target: usize
+}
+
+impl<R: Relation> Related<R>
+{
+    pub fn new(relation: R, target: usize) -> Self
+    {
+        Self { relation, target }
+    }
+
+    /// Returns the id of the target entity
+    pub fn target(&self) -> usize
+    {
+        self.target
+    }
+
+    pub fn relation(&self) -> &R
+    {
+        &self.relation
+    }
+
+    pub fn relation_mut(&mut self) -> &mut R
+    {
+        &mut self.relation
+    }
+}
+
+impl<R: Relation> MapEntities for Related<R>
+{
+    fn map_entities(&mut self, map: &EntityMap) -> bool
+    {
+        map.map(self.target).map(|target| self.target = target).is_some()
+    }
+}
+
+impl<R: Relation> Component for Related<R>
+{
+    type Storage = SparseStore<Self>;
+
+    fn on_register(registry: &mut Registry)
+    {
+        registry.register_map_entities::<Self>();
+    }
+
+    fn on_add(registry: &mut Registry, id: usize)
+    {
+        // a despawned target has no cleanup to run, and its id may be handed to an unrelated entity later
+        if let Some(target) = registry.target_of::<R>(id).filter(|&target| registry.get_entity(target).is_some_and(|e| e.is_active))
+        {
+            registry.index_relation(TypeId::of::<R>(), id, target, R::ON_TARGET_DESPAWN, remove_relation::<R>);
+        }
+    }
+
+    fn on_remove(registry: &mut Registry, id: usize)
+    {
+        if let Some(target) = registry.target_of::<R>(id)
+        {
+            registry.unindex_relation(TypeId::of::<R>(), id, target);
+        }
+    }
+}
+
+/// Removes the relation R from source, used for RelationCleanup::RemoveRelation
+fn remove_relation<R: Relation>(registry: &mut Registry, source: usize)
+{
+    registry.remove_bundle::<(Related<R>,)>(source);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::world::World;
+    use crate::component_store::VecStore;
+
+    use super::*;
+
+    #[test]
+    fn test_sources_of()
+    {
+        let mut world = World::new();
+        let enemy = world.spawn((Ship,));
+        let other = world.spawn((Ship,));
+        let a = world.spawn((Ship,));
+        let b = world.spawn((Ship,));
+
+        world.add_relation(a, Targets, enemy).unwrap();
+        world.add_relation(b, Targets, enemy).unwrap();
+        assert_eq!(world.sources_of::<Targets>(enemy), vec![a, b]);
+        assert_eq!(world.target_of::<Targets>(a), Some(enemy));
+
+        // retargeting moves the source to the new target
+        world.add_relation(b, Targets, other).unwrap();
+        assert_eq!(world.sources_of::<Targets>(enemy), vec![a]);
+        assert_eq!(world.sources_of::<Targets>(other), vec![b]);
+
+        assert_eq!(world.remove_relation::<Targets>(a), Some(enemy));
+        assert!(world.sources_of::<Targets>(enemy).is_empty());
+    }
+
+    #[test]
+    fn test_cleanup_on_target_despawn()
+    {
+        let mut world = World::new();
+        let station = world.spawn((Ship,));
+        let docked = world.spawn((Ship,));
+        let hunter = world.spawn((Ship,));
+        world.add_relation(docked, DockedAt, station).unwrap();
+        world.add_relation(hunter, Targets, station).unwrap();
+
+        world.despawn(station);
+        // the docked ship goes with the station, the hunter only loses its target
+        assert!(!world.get_entity(docked).unwrap().is_active);
+        assert!(world.get_entity(hunter).unwrap().is_active);
+        assert_eq!(world.target_of::<Targets>(hunter), None);
+        assert!(world.sources_of::<DockedAt>(station).is_empty());
+    }
+
+    #[test]
+    fn test_inactive_target()
+    {
+        let mut world = World::new();
+        let target = world.spawn((Ship,));
+        let source = world.spawn((Ship,));
+        world.despawn(target);
+
+        // the despawned target is rejected, so nothing refers to its id
+        assert!(world.add_relation(source, DockedAt, target).is_err());
+        assert!(world.add_relation(target, DockedAt, source).is_err());
+        assert_eq!(world.target_of::<DockedAt>(source), None);
+
+        // despawning the entity that reuses the id doesn't touch the source
+        let reused = world.spawn((Ship,));
+        assert_eq!(reused, target);
+        world.despawn(reused);
+        assert!(world.get_entity(source).unwrap().is_active);
+    }
+
+    struct Targets;
+
+    impl Relation for Targets {}
+
+    struct DockedAt;
+
+    impl Relation for DockedAt
+    {
+        const ON_TARGET_DESPAWN: RelationCleanup = RelationCleanup::DespawnSource;
+    }
+
+    struct Ship;
+
+    impl Component for Ship
+    {
+        type Storage = VecStore<Self>;
+    }
+}
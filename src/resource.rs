@@ -1,13 +1,16 @@
 use std::{
-    any::{Any, TypeId}, 
+    any::{self, Any, TypeId}, 
+    cell::{Ref, RefCell, RefMut},
     collections::HashMap, error, fmt, 
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}
+    ops::{Deref, DerefMut},
+    sync::{atomic::{AtomicU64, Ordering}, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread::{self, ThreadId}
 };
 
 pub struct ResourceError
 {
     // code: usize,
-    message: String
+    pub(crate) message: String
 }
 
 impl fmt::Display for ResourceError
@@ -34,68 +37,172 @@ impl<T: error::Error + Send + Sync + 'static> From<T> for ResourceError
     }
 }
 
-pub trait ResourceType
+/// A type that can be stored in the World as a resource.
+/// Usually implemented with `#[derive(Resource)]`.
+pub trait Resource: Any + Send + Sync {}
+
+pub(crate) trait ResourceType
 {
     type Object: Any + Send + Sync;
 
-    fn get(&self) -> Result<RwLockReadGuard<Self::Object>, ResourceError>;
+    fn get(&self) -> Result<RwLockReadGuard<'_, Self::Object>, ResourceError>;
 
-    fn get_mut(&mut self) -> Result<RwLockWriteGuard<Self::Object>, ResourceError>;
+    #[allow(dead_code)]
+    fn get_mut(&mut self) -> Result<RwLockWriteGuard<'_, Self::Object>, ResourceError>;
 }
 
-pub struct Resource<T>
+/// Storage for a single resource and its change ticks
+pub(crate) struct ResourceCell<T>
 {
-    data: RwLock<T>
+    data: RwLock<T>,
+    added: u64, // change tick the resource was inserted at
+    changed: AtomicU64 // change tick the resource was last mutably accessed at
 }
 
-impl<T> Resource<T>
+impl<T> ResourceCell<T>
 {
-    pub fn new(data: T) -> Self
+    pub fn new(data: T, change_tick: u64) -> Self
     {
         Self
         {
-            data: RwLock::new(data)
+            data: RwLock::new(data),
+            added: change_tick,
+            changed: AtomicU64::new(change_tick)
         }
     }
+
+    /// Returns the change tick the resource was last mutably accessed at
+    pub fn changed_tick(&self) -> u64
+    {
+        self.changed.load(Ordering::Acquire)
+    }
+
+    /// Consumes the ResourceCell and returns the underlying data
+    pub fn into_inner(self) -> T
+    {
+        // a poisoned lock still holds valid data
+        self.data.into_inner().unwrap_or_else(|err| err.into_inner())
+    }
 }
 
-impl<T: Send + Sync + 'static> ResourceType for Resource<T>
+impl<T: Send + Sync + 'static> ResourceType for ResourceCell<T>
 {
     type Object = T;
 
-    fn get(&self) -> Result<RwLockReadGuard<Self::Object>, ResourceError>
+    fn get(&self) -> Result<RwLockReadGuard<'_, Self::Object>, ResourceError>
     {
         match self.data.read()
         {
             Ok(data) => Ok(data),
             Err(err) => 
             {
-                let type_id = TypeId::of::<T>();
-                let comp_error = ResourceError { message: format!("Failed to read resource with type id {type_id:?}: {err:?}") };
+                let type_name = any::type_name::<T>();
+                let comp_error = ResourceError { message: format!("Failed to read resource of type {type_name}: {err:?}") };
                 Err(comp_error)
             }
         }
     }
 
-    fn get_mut(&mut self) -> Result<RwLockWriteGuard<Self::Object>, ResourceError>
+    fn get_mut(&mut self) -> Result<RwLockWriteGuard<'_, Self::Object>, ResourceError>
     {
         match self.data.write()
         {
             Ok(data) => Ok(data),
             Err(err) => 
             {
-                let type_id = TypeId::of::<T>();
-                let comp_error = ResourceError { message: format!("Failed to write resource with type id {type_id:?}: {err:?}") };
+                let type_name = any::type_name::<T>();
+                let comp_error = ResourceError { message: format!("Failed to write resource of type {type_name}: {err:?}") };
                 Err(comp_error)
             }
         }
     }
 }
 
+/// Read access to a resource, with change detection
+pub struct Res<'a, T>
+{
+    guard: RwLockReadGuard<'a, T>,
+    added: u64,
+    changed: u64,
+    last_run: u64
+}
+
+impl<'a, T> Res<'a, T>
+{
+    /// Returns true if the resource was inserted after the last run of the observing system
+    pub fn is_added(&self) -> bool
+    {
+        self.added > self.last_run
+    }
+
+    /// Returns true if the resource was inserted or mutated after the last run of the observing system
+    pub fn is_changed(&self) -> bool
+    {
+        self.changed > self.last_run
+    }
+}
+
+impl<'a, T> Deref for Res<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.guard
+    }
+}
+
+/// Write access to a resource, with change detection.
+/// Mutably dereferencing it marks the resource as changed.
+pub struct ResMut<'a, T>
+{
+    guard: RwLockWriteGuard<'a, T>,
+    added: u64,
+    changed: &'a AtomicU64,
+    last_run: u64,
+    this_run: u64
+}
+
+impl<'a, T> ResMut<'a, T>
+{
+    /// Returns true if the resource was inserted after the last run of the observing system
+    pub fn is_added(&self) -> bool
+    {
+        self.added > self.last_run
+    }
+
+    /// Returns true if the resource was inserted or mutated after the last run of the observing system
+    pub fn is_changed(&self) -> bool
+    {
+        self.changed.load(Ordering::Acquire) > self.last_run
+    }
+}
+
+impl<'a, T> Deref for ResMut<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for ResMut<'a, T>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        self.changed.store(self.this_run, Ordering::Release);
+        &mut self.guard
+    }
+}
+
 pub struct Resources
 {
     // data: HashMap<TypeId, Arc<RwLock<dyn Any>>>,
-    data: HashMap<TypeId, Box<dyn Any>>
+    data: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    pub(crate) change_tick: u64, // tick inserted and mutated resources are marked with
+    pub(crate) last_change_tick: u64 // tick changes are detected relative to
 }
 
 impl Resources
@@ -104,22 +211,25 @@ impl Resources
     {
         Self
         {
-            data: HashMap::new()
+            data: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0
         }
     }
 
-    pub fn add<T: Any>(&mut self, data: T)
+    pub fn add<T: Resource>(&mut self, data: T)
     {
-        let type_id = data.type_id();
+        let type_id = TypeId::of::<T>();
         // self.data.insert(type_id, Arc::new(RwLock::new(data)));
         // self.data.insert(type_id, Box::new(data));
-        self.data.insert(type_id, Box::new(Resource::new(data)));
+        self.data.insert(type_id, Box::new(ResourceCell::new(data, self.change_tick)));
     }
 
     // pub fn get<T: Any>(&self) -> Option<&Box<T>>
-    pub fn get<T: Send + Sync + 'static>(&self) -> Result<RwLockReadGuard<T>, ResourceError>
+    pub fn get<T: Resource>(&self) -> Result<Res<'_, T>, ResourceError>
     {
         let type_id = TypeId::of::<T>();
+        let type_name = any::type_name::<T>();
         // if let Some(data) = self.data.get(&type_id)
         // {
         //     data.downcast_ref::<Box<T>>()
@@ -130,17 +240,18 @@ impl Resources
         {
             Some(data) => 
             {
-                let d = data.downcast_ref::<Resource<T>>().expect(format!("Failed to get resource of type {type_id:?}").as_str());
-                Ok(d.get()?)
+                let d = data.downcast_ref::<ResourceCell<T>>().unwrap_or_else(|| panic!("Failed to get resource of type {type_name}"));
+                Ok(Res { guard: d.get()?, added: d.added, changed: d.changed_tick(), last_run: self.last_change_tick })
             },
-            None => Err(ResourceError { message: format!("Failed to get resource of type {type_id:?}") })
+            None => Err(ResourceError { message: format!("Failed to get resource of type {type_name}") })
         }
     }
 
     // pub fn get_mut<T: Any>(&mut self) -> Option<&mut T>
-    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Result<RwLockWriteGuard<T>, ResourceError>
+    pub fn get_mut<T: Resource>(&mut self) -> Result<ResMut<'_, T>, ResourceError>
     {
         let type_id = TypeId::of::<T>();
+        let type_name = any::type_name::<T>();
         // if let Some(data) = self.data.get_mut(&type_id)
         // {
         //     data.downcast_mut::<T>()
@@ -151,24 +262,284 @@ impl Resources
         {
             Some(data) => 
             {
-                let d = data.downcast_mut::<Resource<T>>().expect(format!("Failed to get resource of type {type_id:?}").as_str());
-                Ok(d.get_mut()?)
+                let d = data.downcast_mut::<ResourceCell<T>>().unwrap_or_else(|| panic!("Failed to get resource of type {type_name}"));
+                let (added, last_run, this_run) = (d.added, self.last_change_tick, self.change_tick);
+                let guard = d.data.write().map_err(|err| ResourceError { message: format!("Failed to write resource of type {type_name}: {err:?}") })?;
+                Ok(ResMut { guard, added, changed: &d.changed, last_run, this_run })
             },
-            None => Err(ResourceError { message: format!("Failed to get resource of type {type_id:?}") })
+            None => Err(ResourceError { message: format!("Failed to get resource of type {type_name}") })
+        }
+    }
+
+    /// Retrieves a resource without blocking, fails if it is currently mutably borrowed
+    pub fn try_get<T: Resource>(&self) -> Result<Res<'_, T>, ResourceError>
+    {
+        let d = self.get_cell::<T>()?;
+        let guard = d.data.try_read().map_err(|err| ResourceError { message: format!("Failed to read resource of type {}: {err:?}", any::type_name::<T>()) })?;
+        Ok(Res { guard, added: d.added, changed: d.changed_tick(), last_run: self.last_change_tick })
+    }
+
+    /// Retrieves a mutable resource through a shared reference without blocking, fails if it is currently borrowed
+    pub fn try_get_mut<T: Resource>(&self) -> Result<ResMut<'_, T>, ResourceError>
+    {
+        let d = self.get_cell::<T>()?;
+        let guard = d.data.try_write().map_err(|err| ResourceError { message: format!("Failed to write resource of type {}: {err:?}", any::type_name::<T>()) })?;
+        Ok(ResMut { guard, added: d.added, changed: &d.changed, last_run: self.last_change_tick, this_run: self.change_tick })
+    }
+
+    fn get_cell<T: Resource>(&self) -> Result<&ResourceCell<T>, ResourceError>
+    {
+        let type_id = TypeId::of::<T>();
+        let type_name = any::type_name::<T>();
+        match self.data.get(&type_id)
+        {
+            Some(data) => Ok(data.downcast_ref::<ResourceCell<T>>().unwrap_or_else(|| panic!("Failed to get resource of type {type_name}"))),
+            None => Err(ResourceError { message: format!("Failed to get resource of type {type_name}") })
         }
     }
 
-    pub fn remove<T: Any>(&mut self)
+    /// Removes a resource and returns it if it existed
+    pub fn remove<T: Resource>(&mut self) -> Option<T>
+    {
+        self.take::<T>().map(|(data, _added)| data)
+    }
+
+    /// Removes a resource and returns it along with the tick it was added at
+    pub(crate) fn take<T: Resource>(&mut self) -> Option<(T, u64)>
     {
         let type_id = TypeId::of::<T>();
-        self.data.remove(&type_id);
+        let type_name = any::type_name::<T>();
+        let data = self.data.remove(&type_id)?;
+        let resource = data.downcast::<ResourceCell<T>>().unwrap_or_else(|_| panic!("Failed to remove resource of type {type_name}"));
+        let added = resource.added;
+        Some((resource.into_inner(), added))
+    }
+
+    /// Reinserts a resource removed with take, keeping its added tick and marking it as changed
+    pub(crate) fn restore<T: Resource>(&mut self, data: T, added: u64)
+    {
+        let mut resource = ResourceCell::new(data, self.change_tick);
+        resource.added = added;
+        self.data.insert(TypeId::of::<T>(), Box::new(resource));
+    }
+
+    /// Returns true if a resource of type T exists
+    pub fn contains<T: Any>(&self) -> bool
+    {
+        self.data.contains_key(&TypeId::of::<T>())
     }
     
 }
 
+impl Default for Resources
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+/// A resource that is not Send or Sync, pinned to the thread that inserted it
+struct NonSendResource
+{
+    data: RefCell<Box<dyn Any>>,
+    type_name: &'static str,
+    thread_id: ThreadId
+}
+
+impl NonSendResource
+{
+    /// Returns an error if the current thread is not the one that inserted the resource
+    fn validate_thread(&self) -> Result<(), ResourceError>
+    {
+        let current = thread::current().id();
+        if current != self.thread_id
+        {
+            return Err(ResourceError { message: format!("Non-send resource of type {} was accessed from thread {current:?}, but is pinned to thread {:?}", self.type_name, self.thread_id) });
+        }
+        Ok(())
+    }
+}
+
+/// Read access to a non-send resource
+pub struct NonSend<'a, T: 'static>
+{
+    resource: Ref<'a, T>
+}
+
+impl<'a, T: 'static> Deref for NonSend<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.resource
+    }
+}
+
+/// Write access to a non-send resource
+pub struct NonSendMut<'a, T: 'static>
+{
+    resource: RefMut<'a, T>
+}
+
+impl<'a, T: 'static> Deref for NonSendMut<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.resource
+    }
+}
+
+impl<'a, T: 'static> DerefMut for NonSendMut<'a, T>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        &mut self.resource
+    }
+}
+
+/// Storage for resources that are not Send or Sync.
+/// Every resource is pinned to the thread that inserted it and can only be accessed from that thread.
+pub struct NonSendResources
+{
+    data: HashMap<TypeId, NonSendResource>
+}
+
+// SAFETY: the stored values and their borrow flags are only ever accessed, replaced or dropped
+// after validating that the current thread is the one that inserted them.
+unsafe impl Send for NonSendResources {}
+unsafe impl Sync for NonSendResources {}
+
+impl NonSendResources
+{
+    pub fn new() -> Self
+    {
+        Self
+        {
+            data: HashMap::new()
+        }
+    }
+
+    /// Adds a resource pinned to the current thread.
+    /// Panics if it replaces a resource pinned to another thread.
+    pub fn add<T: 'static>(&mut self, data: T)
+    {
+        let type_id = TypeId::of::<T>();
+        if let Some(old) = self.data.get(&type_id)
+        {
+            if let Err(err) = old.validate_thread()
+            {
+                panic!("{err}");
+            }
+        }
+        self.data.insert(type_id, NonSendResource { data: RefCell::new(Box::new(data)), type_name: any::type_name::<T>(), thread_id: thread::current().id() });
+    }
+
+    /// Fails if the resource is missing, pinned to another thread or currently mutably borrowed
+    pub fn get<T: 'static>(&self) -> Result<NonSend<'_, T>, ResourceError>
+    {
+        let type_name = any::type_name::<T>();
+        let data = self.get_data::<T>()?
+            .try_borrow()
+            .map_err(|err| ResourceError { message: format!("Failed to read non-send resource of type {type_name}: {err}") })?;
+        let resource = Ref::map(data, |data| data.downcast_ref::<T>().unwrap_or_else(|| panic!("Failed to get non-send resource of type {type_name}")));
+        Ok(NonSend { resource })
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Result<NonSendMut<'_, T>, ResourceError>
+    {
+        self.try_get_mut::<T>()
+    }
+
+    /// Retrieves a mutable non-send resource through a shared reference, fails if it is currently borrowed
+    pub fn try_get_mut<T: 'static>(&self) -> Result<NonSendMut<'_, T>, ResourceError>
+    {
+        let type_name = any::type_name::<T>();
+        let data = self.get_data::<T>()?
+            .try_borrow_mut()
+            .map_err(|err| ResourceError { message: format!("Failed to write non-send resource of type {type_name}: {err}") })?;
+        let resource = RefMut::map(data, |data| data.downcast_mut::<T>().unwrap_or_else(|| panic!("Failed to get non-send resource of type {type_name}")));
+        Ok(NonSendMut { resource })
+    }
+
+    fn get_data<T: 'static>(&self) -> Result<&RefCell<Box<dyn Any>>, ResourceError>
+    {
+        match self.data.get(&TypeId::of::<T>())
+        {
+            Some(resource) =>
+            {
+                resource.validate_thread()?;
+                Ok(&resource.data)
+            },
+            None => Err(ResourceError { message: format!("Failed to get non-send resource of type {}", any::type_name::<T>()) })
+        }
+    }
+
+    /// Returns true if a non-send resource of type T exists
+    pub fn contains<T: 'static>(&self) -> bool
+    {
+        self.data.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Fails if the non-send resource of type T is pinned to a thread other than the current one
+    pub fn validate_thread<T: 'static>(&self) -> Result<(), ResourceError>
+    {
+        match self.data.get(&TypeId::of::<T>())
+        {
+            Some(resource) => resource.validate_thread(),
+            None => Ok(())
+        }
+    }
+
+    /// Fails if any non-send resource is pinned to a thread other than the current one
+    pub fn validate_thread_all(&self) -> Result<(), ResourceError>
+    {
+        self.data.values().try_for_each(NonSendResource::validate_thread)
+    }
+
+    /// Removes a non-send resource, fails if called from a thread other than the one that inserted it
+    pub fn remove<T: 'static>(&mut self) -> Result<(), ResourceError>
+    {
+        let type_id = TypeId::of::<T>();
+        if let Some(resource) = self.data.get(&type_id)
+        {
+            resource.validate_thread()?;
+            self.data.remove(&type_id);
+        }
+        Ok(())
+    }
+}
+
+impl Default for NonSendResources
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl Drop for NonSendResources
+{
+    fn drop(&mut self)
+    {
+        // Dropping a value on another thread is as unsafe as accessing it there,
+        // so resources pinned to another thread are leaked instead of panicking in drop
+        let (pinned, leaked): (HashMap<_, _>, HashMap<_, _>) = std::mem::take(&mut self.data)
+            .into_iter()
+            .partition(|(_, resource)| resource.validate_thread().is_ok());
+        std::mem::forget(leaked);
+        drop(pinned);
+    }
+}
+
 #[cfg(test)]
 mod tests
 {
+    use std::{cell::Cell, rc::Rc};
+
     use super::*;
 
     #[test]
@@ -186,14 +557,94 @@ mod tests
         assert_eq!(delta_time.value, 5.0);
     }
 
+    #[test]
+    fn test_remove_resource()
+    {
+        let mut resources = Resources::new();
+        resources.add(DeltaTime{ value: 2.0 });
+
+        assert!(resources.contains::<DeltaTime>());
+        assert_eq!(resources.remove::<DeltaTime>().map(|dt| dt.value), Some(2.0));
+        assert!(!resources.contains::<DeltaTime>());
+        assert!(resources.remove::<DeltaTime>().is_none());
+    }
+
+    #[test]
+    fn test_resource_change_detection()
+    {
+        let mut resources = Resources::new();
+        resources.add(DeltaTime{ value: 1.0 });
+        assert!(resources.get::<DeltaTime>().unwrap().is_added());
+
+        // advance past the insertion tick
+        resources.last_change_tick = resources.change_tick;
+        resources.change_tick += 1;
+        assert!(!resources.get::<DeltaTime>().unwrap().is_added());
+        assert!(!resources.get::<DeltaTime>().unwrap().is_changed());
+
+        // reading through ResMut doesn't mark a change
+        let delta_time = resources.get_mut::<DeltaTime>().unwrap();
+        assert_eq!(delta_time.value, 1.0);
+        drop(delta_time);
+        assert!(!resources.get::<DeltaTime>().unwrap().is_changed());
+
+        resources.get_mut::<DeltaTime>().unwrap().value = 2.0;
+        assert!(resources.get::<DeltaTime>().unwrap().is_changed());
+        assert!(!resources.get::<DeltaTime>().unwrap().is_added());
+    }
+
+    #[test]
+    fn test_non_send_resources()
+    {
+        let mut resources = NonSendResources::new();
+        resources.add(Rc::new(Cell::new(1)));
+
+        resources.get::<Rc<Cell<i32>>>().unwrap().set(2);
+        assert_eq!(resources.get::<Rc<Cell<i32>>>().unwrap().get(), 2);
+
+        thread::scope(|s|
+            {
+                s.spawn(||
+                {
+                    assert!(resources.get::<Rc<Cell<i32>>>().is_err());
+                });
+            }
+        );
+
+        assert!(resources.remove::<Rc<Cell<i32>>>().is_ok());
+        assert!(resources.get::<Rc<Cell<i32>>>().is_err());
+    }
+
+    #[test]
+    fn test_non_send_resources_dropped_elsewhere()
+    {
+        let shared = Rc::new(());
+        let mut resources = NonSendResources::new();
+        resources.add(shared.clone());
+        {
+            let _borrowed = resources.get::<Rc<()>>().unwrap();
+            assert!(resources.try_get_mut::<Rc<()>>().is_err());
+        }
+        assert!(resources.try_get_mut::<Rc<()>>().is_ok());
+
+        // dropping on another thread leaks the resource rather than panicking
+        thread::scope(|s| s.spawn(move || drop(resources)).join()).unwrap();
+        assert_eq!(Rc::strong_count(&shared), 2);
+    }
+
     pub struct DeltaTime
     {
         pub value: f32
     }
 
+    impl Resource for DeltaTime {}
+
+    #[allow(dead_code)]
     pub struct ScreenSize
     {
         pub width: f32,
         pub height: f32
     }
+
+    impl Resource for ScreenSize {}
 }
\ No newline at end of file
@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// Error saving or loading a scene
+pub struct SceneError
+{
+    pub(crate) message: String
+}
+
+impl fmt::Display for SceneError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for SceneError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl<T: error::Error + Send + Sync + 'static> From<T> for SceneError
+{
+    fn from(e: T) -> Self
+    {
+        Self { message: e.to_string() }
+    }
+}
+
+/// Entities, their components and resources saved from a World, keyed by registered type names
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Scene
+{
+    pub entities: Vec<SceneEntity>,
+    pub resources: BTreeMap<String, serde_json::Value>
+}
+
+/// An entity saved in a scene, id is the id it had in the World it was saved from
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SceneEntity
+{
+    pub id: usize,
+    pub components: BTreeMap<String, serde_json::Value>
+}
+
+impl Scene
+{
+    pub fn to_json(&self) -> Result<String, SceneError>
+    {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SceneError>
+    {
+        Ok(serde_json::from_str(json)?)
+    }
+}
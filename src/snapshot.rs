@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scene::SceneError;
+
+/// Binary copy of a World's entities, registered components and registered resources.
+/// Unlike a Scene, restoring a snapshot keeps every entity id, including the free list of dropped ids.
+/// Change ticks and dynamic components aren't copied, see World::restore.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot
+{
+    pub(crate) active: Vec<bool>, // is_active of every entity slot
+    pub(crate) dropped: Vec<usize>, // dropped ids in the order they are reused from the back
+    pub(crate) components: Vec<ComponentColumn>,
+    pub(crate) resources: Vec<(String, Vec<u8>)>
+}
+
+/// Every stored value of one component type, keyed by entity id
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct ComponentColumn
+{
+    pub(crate) name: String,
+    pub(crate) values: Vec<(usize, Vec<u8>)>
+}
+
+impl Snapshot
+{
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SceneError>
+    {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SceneError>
+    {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Returns the number of entity slots, active or dropped
+    pub fn entity_slots(&self) -> usize
+    {
+        self.active.len()
+    }
+}
+
+/// Changes that bring a World from one state to another: spawned and despawned entities,
+/// inserted, changed and removed components, and changed or removed resources
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Delta
+{
+    pub(crate) slots: usize, // entity slots in the new state
+    pub(crate) dropped: Vec<usize>, // free list of the new state
+    pub(crate) spawned: Vec<usize>,
+    pub(crate) despawned: Vec<usize>,
+    pub(crate) inserted: Vec<ComponentColumn>,
+    pub(crate) changed: Vec<ComponentColumn>,
+    pub(crate) removed: Vec<(String, Vec<usize>)>,
+    pub(crate) resources: Vec<(String, Vec<u8>)>,
+    pub(crate) removed_resources: Vec<String>
+}
+
+impl Delta
+{
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SceneError>
+    {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SceneError>
+    {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Returns the ids of entities that were spawned
+    pub fn spawned(&self) -> &[usize]
+    {
+        &self.spawned
+    }
+
+    /// Returns the ids of entities that were despawned
+    pub fn despawned(&self) -> &[usize]
+    {
+        &self.despawned
+    }
+
+    /// Returns the ids of entities that got a component of the registered type name
+    pub fn inserted(&self, name: &str) -> Vec<usize>
+    {
+        column_ids(&self.inserted, name)
+    }
+
+    /// Returns the ids of entities whose component of the registered type name changed value
+    pub fn changed(&self, name: &str) -> Vec<usize>
+    {
+        column_ids(&self.changed, name)
+    }
+
+    /// Returns the ids of entities that lost a component of the registered type name
+    pub fn removed(&self, name: &str) -> Vec<usize>
+    {
+        self.removed.iter().find(|(removed, _)| removed == name).map(|(_, ids)| ids.clone()).unwrap_or_default()
+    }
+
+    /// Returns true if nothing changed besides the entity allocator
+    pub fn is_empty(&self) -> bool
+    {
+        self.spawned.is_empty() && self.despawned.is_empty() && self.inserted.is_empty() && self.changed.is_empty()
+            && self.removed.is_empty() && self.resources.is_empty() && self.removed_resources.is_empty()
+    }
+}
+
+fn column_ids(columns: &[ComponentColumn], name: &str) -> Vec<usize>
+{
+    columns.iter().find(|column| column.name == name).map(|column| column.values.iter().map(|(id, _)| *id).collect()).unwrap_or_default()
+}
+
+/// Values of every column keyed by type name then entity id
+fn index_columns(columns: &[ComponentColumn]) -> HashMap<&str, HashMap<usize, &[u8]>>
+{
+    columns.iter()
+        .map(|column| (column.name.as_str(), column.values.iter().map(|(id, bytes)| (*id, bytes.as_slice())).collect()))
+        .collect()
+}
+
+impl Snapshot
+{
+    /// Returns the changes that bring a World in the state of self to the state of new
+    pub fn delta(&self, new: &Snapshot) -> Delta
+    {
+        let is_active = | active: &[bool], id: usize | active.get(id).copied().unwrap_or(false);
+        let mut delta = Delta {
+            slots: new.active.len(),
+            dropped: new.dropped.clone(),
+            spawned: (0..new.active.len()).filter(|&id| new.active[id] && !is_active(&self.active, id)).collect(),
+            despawned: (0..self.active.len()).filter(|&id| self.active[id] && !is_active(&new.active, id)).collect(),
+            ..Delta::default()
+        };
+
+        let old_columns = index_columns(&self.components);
+        for column in &new.components
+        {
+            let old_values = old_columns.get(column.name.as_str());
+            let mut inserted = ComponentColumn { name: column.name.clone(), values: Vec::new() };
+            let mut changed = ComponentColumn { name: column.name.clone(), values: Vec::new() };
+            for (id, bytes) in &column.values
+            {
+                match old_values.and_then(|values| values.get(id))
+                {
+                    None => inserted.values.push((*id, bytes.clone())),
+                    Some(old) if *old != bytes.as_slice() => changed.values.push((*id, bytes.clone())),
+                    Some(_) => {}
+                }
+            }
+            delta.push_columns(inserted, changed);
+        }
+
+        let new_columns = index_columns(&new.components);
+        for column in &self.components
+        {
+            let new_values = new_columns.get(column.name.as_str());
+            let removed: Vec<usize> = column.values.iter()
+                .map(|(id, _)| *id)
+                .filter(|&id| is_active(&new.active, id) && !new_values.is_some_and(|values| values.contains_key(&id)))
+                .collect();
+            if !removed.is_empty()
+            {
+                delta.removed.push((column.name.clone(), removed));
+            }
+        }
+
+        delta.diff_resources(&self.resources, &new.resources);
+        delta
+    }
+}
+
+impl Delta
+{
+    /// Adds the non empty columns
+    pub(crate) fn push_columns(&mut self, inserted: ComponentColumn, changed: ComponentColumn)
+    {
+        if !inserted.values.is_empty()
+        {
+            self.inserted.push(inserted);
+        }
+        if !changed.values.is_empty()
+        {
+            self.changed.push(changed);
+        }
+    }
+
+    /// Records resources that are new or differ in new, and resources missing from new
+    pub(crate) fn diff_resources(&mut self, old: &[(String, Vec<u8>)], new: &[(String, Vec<u8>)])
+    {
+        for (name, bytes) in new
+        {
+            if !old.iter().any(|(old_name, old_bytes)| old_name == name && old_bytes == bytes)
+            {
+                self.resources.push((name.clone(), bytes.clone()));
+            }
+        }
+        for (name, _) in old
+        {
+            if !new.iter().any(|(new_name, _)| new_name == name)
+            {
+                self.removed_resources.push(name.clone());
+            }
+        }
+    }
+}
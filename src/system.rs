@@ -1,18 +1,222 @@
 use std::{
-    any::{Any, TypeId}, 
-    collections::HashMap, ops::Deref
+    any::{self, Any, TypeId}, 
+    collections::HashMap, ops::Deref,
+    thread
 };
 
+use crate::resource::{NonSend, NonSendMut, Res, ResMut, Resource, ResourceError};
+
 use super::world::World;
 
-pub trait System
+pub trait System: Any
 {
     fn run(&mut self, world: &mut World);
+
+    /// Returns false if the system accesses non-send resources, which must be used on the thread they are pinned to
+    fn is_send(&self) -> bool
+    {
+        true
+    }
+
+    /// Fails if the non-send resources the system accesses are pinned to another thread.
+    /// Only checked for systems that are not send, by default against every non-send resource.
+    fn validate_thread(&self, world: &World) -> Result<(), ResourceError>
+    {
+        world.validate_non_send_threads()
+    }
+}
+
+/// A system that declares the SystemParams it needs instead of taking the World.
+/// The params are fetched before every run, and every ParamSystem can be added to a Dispatch as a System.
+pub trait ParamSystem: Any
+{
+    type Param<'w>: SystemParam<'w>;
+
+    fn run_with(&mut self, param: Self::Param<'_>);
+}
+
+impl<S: ParamSystem> System for S
+{
+    fn run(&mut self, world: &mut World)
+    {
+        let param = S::Param::fetch(world)
+            .unwrap_or_else(|err| panic!("Failed to fetch the params of system {}: {err}", any::type_name::<S>()));
+        self.run_with(param);
+    }
+
+    fn is_send(&self) -> bool
+    {
+        S::Param::is_send()
+    }
+
+    fn validate_thread(&self, world: &World) -> Result<(), ResourceError>
+    {
+        S::Param::validate_thread(world)
+    }
+}
+
+/// Data fetched from a World for the lifetime 'w, such as resources.
+/// Structs whose fields are all SystemParams can group them with `#[derive(SystemParam)]`.
+pub trait SystemParam<'w>: Sized
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>;
+
+    /// Returns false if the param accesses non-send resources
+    fn is_send() -> bool
+    {
+        true
+    }
+
+    /// Fails if a non-send resource the param accesses is pinned to another thread
+    fn validate_thread(_world: &World) -> Result<(), ResourceError>
+    {
+        Ok(())
+    }
+}
+
+impl<'w> SystemParam<'w> for &'w World
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        Ok(world)
+    }
+}
+
+impl<'w, T: Resource> SystemParam<'w> for Res<'w, T>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        world.try_get_resource::<T>()
+    }
+}
+
+impl<'w, T: Resource> SystemParam<'w> for ResMut<'w, T>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        world.try_get_resource_mut::<T>()
+    }
+}
+
+impl<'w, T: 'static> SystemParam<'w> for NonSend<'w, T>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        world.get_non_send_resource::<T>()
+    }
+
+    fn is_send() -> bool
+    {
+        false
+    }
+
+    fn validate_thread(world: &World) -> Result<(), ResourceError>
+    {
+        world.validate_non_send_thread::<T>()
+    }
+}
+
+impl<'w, T: 'static> SystemParam<'w> for NonSendMut<'w, T>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        world.try_get_non_send_resource_mut::<T>()
+    }
+
+    fn is_send() -> bool
+    {
+        false
+    }
+
+    fn validate_thread(world: &World) -> Result<(), ResourceError>
+    {
+        world.validate_non_send_thread::<T>()
+    }
+}
+
+impl<'w, P: SystemParam<'w>> SystemParam<'w> for Option<P>
+{
+    fn fetch(world: &'w World) -> Result<Self, ResourceError>
+    {
+        Ok(P::fetch(world).ok())
+    }
+
+    fn is_send() -> bool
+    {
+        P::is_send()
+    }
+
+    fn validate_thread(world: &World) -> Result<(), ResourceError>
+    {
+        P::validate_thread(world)
+    }
+}
+
+macro_rules! impl_system_param_for_tuple
+{
+    ($($name: ident),*) =>
+    {
+        impl<'w, $($name: SystemParam<'w>),*> SystemParam<'w> for ($($name,)*)
+        {
+            fn fetch(world: &'w World) -> Result<Self, ResourceError>
+            {
+                Ok(($($name::fetch(world)?,)*))
+            }
+
+            fn is_send() -> bool
+            {
+                true $(&& $name::is_send())*
+            }
+
+            fn validate_thread(world: &World) -> Result<(), ResourceError>
+            {
+                $($name::validate_thread(world)?;)*
+                Ok(())
+            }
+        }
+    }
+}
+
+impl_system_param_for_tuple!(A);
+impl_system_param_for_tuple!(A, B);
+impl_system_param_for_tuple!(A, B, C);
+impl_system_param_for_tuple!(A, B, C, D);
+impl_system_param_for_tuple!(A, B, C, D, E);
+impl_system_param_for_tuple!(A, B, C, D, E, F);
+impl_system_param_for_tuple!(A, B, C, D, E, F, G);
+impl_system_param_for_tuple!(A, B, C, D, E, F, G, H);
+
+/// Condition evaluated before a system runs, the system is skipped if it returns false
+pub type RunCondition = Box<dyn Fn(&World) -> bool>;
+
+/// Run condition that is true if the resource was inserted or mutated since the system last ran
+pub fn resource_changed<T: Resource>() -> impl Fn(&World) -> bool
+{
+    | world: &World | world.get_resource::<T>().is_ok_and(|res| res.is_changed())
+}
+
+/// Run condition that is true if the resource was inserted since the system last ran
+pub fn resource_added<T: Resource>() -> impl Fn(&World) -> bool
+{
+    | world: &World | world.get_resource::<T>().is_ok_and(|res| res.is_added())
+}
+
+/// Run condition that is true if the resource exists
+pub fn resource_exists<T: Resource>() -> impl Fn(&World) -> bool
+{
+    | world: &World | world.contains_resource::<T>()
+}
+
+struct SystemEntry
+{
+    system: Box<dyn System>,
+    conditions: Vec<RunCondition>,
+    last_run: u64 // change tick of the last time the system ran
 }
 
 pub struct Dispatch
 {
-    systems: HashMap<TypeId, Box<dyn System>>
+    systems: HashMap<TypeId, SystemEntry>
 }
 
 impl Dispatch
@@ -27,7 +231,24 @@ impl Dispatch
     pub fn add_system(&mut self, system: Box<dyn System>)
     {
         let type_id = system.deref().type_id();
-        self.systems.insert(type_id, system);
+        self.systems.insert(type_id, SystemEntry { system, conditions: Vec::new(), last_run: 0 });
+    }
+
+    /// Adds a system that only runs when the condition is true
+    pub fn add_system_with_condition(&mut self, system: Box<dyn System>, condition: impl Fn(&World) -> bool + 'static)
+    {
+        let type_id = system.deref().type_id();
+        self.systems.insert(type_id, SystemEntry { system, conditions: vec![Box::new(condition)], last_run: 0 });
+    }
+
+    /// Adds a run condition to an already added system of type T
+    pub fn add_run_condition<T: System>(&mut self, condition: impl Fn(&World) -> bool + 'static)
+    {
+        let type_id = TypeId::of::<T>();
+        if let Some(entry) = self.systems.get_mut(&type_id)
+        {
+            entry.conditions.push(Box::new(condition));
+        }
     }
 
     pub fn remove_system<T: Any>(&mut self)
@@ -38,9 +259,218 @@ impl Dispatch
 
     pub fn dispatch_systems(&mut self, world: &mut World)
     {
-        for system in self.systems.values_mut()
+        let last_change_tick = world.last_change_tick();
+        for entry in self.systems.values_mut()
         {
-            system.run(world);
+            // changes are detected relative to the last time this system ran
+            world.set_last_change_tick(entry.last_run);
+            if !entry.conditions.iter().all(|condition| condition(world))
+            {
+                continue;
+            }
+
+            // systems using non-send resources are only allowed on the threads those resources are pinned to
+            if !entry.system.is_send()
+            {
+                if let Err(err) = entry.system.validate_thread(world)
+                {
+                    panic!("System accessing non-send resources can't run on thread {:?}: {err}", thread::current().id());
+                }
+            }
+            entry.system.run(world);
+            world.flush();
+            entry.last_run = world.increment_change_tick();
         }
+        world.set_last_change_tick(last_change_tick);
+    }
+}
+
+impl Default for Dispatch
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{cell::Cell, rc::Rc, sync::mpsc};
+
+    use super::*;
+
+    #[test]
+    fn test_resource_changed_condition()
+    {
+        let mut world = World::new();
+        world.add_resource(Settings{ rebuilds: 0, value: 1 });
+
+        let mut dispatch = Dispatch::new();
+        dispatch.add_system_with_condition(Box::new(RebuildCache), resource_changed::<Settings>());
+        dispatch.add_system(Box::new(CountFrames));
+
+        // the resource was added since the system last ran
+        dispatch.dispatch_systems(&mut world);
+        assert_eq!(world.get_resource::<Settings>().unwrap().rebuilds, 1);
+        assert_eq!(world.get_resource::<Frames>().unwrap().count, 1);
+
+        // nothing changed, the system's own mutation is not seen as a change
+        dispatch.dispatch_systems(&mut world);
+        assert_eq!(world.get_resource::<Settings>().unwrap().rebuilds, 1);
+        assert_eq!(world.get_resource::<Frames>().unwrap().count, 2);
+
+        world.get_resource_mut::<Settings>().unwrap().value = 2;
+        dispatch.dispatch_systems(&mut world);
+        assert_eq!(world.get_resource::<Settings>().unwrap().rebuilds, 2);
+    }
+
+    #[test]
+    fn test_param_system()
+    {
+        let mut world = World::new();
+        world.add_resource(Settings{ rebuilds: 0, value: 3 });
+        world.add_resource(Frames{ count: 0 });
+
+        let mut dispatch = Dispatch::new();
+        dispatch.add_system(Box::new(AddValue));
+        dispatch.dispatch_systems(&mut world);
+        dispatch.dispatch_systems(&mut world);
+        assert_eq!(world.get_resource::<Frames>().unwrap().count, 6);
+    }
+
+    #[test]
+    fn test_non_send_system_thread()
+    {
+        let mut world = World::new();
+        world.add_resource(Frames{ count: 0 });
+        world.insert_non_send_resource(Rc::new(Cell::new(0u32)));
+        assert!(!CountCalls.is_send());
+        assert!(AddValue.is_send());
+
+        // a non-send resource pinned to another thread doesn't stop systems that don't use it
+        let (to_worker, from_main) = mpsc::channel::<World>();
+        let (to_main, from_worker) = mpsc::channel::<World>();
+        thread::scope(|s|
+        {
+            s.spawn(move ||
+            {
+                let mut world = from_main.recv().unwrap();
+                world.insert_non_send_resource(0u8);
+                to_main.send(world).unwrap();
+
+                // non-send resources are leaked unless dropped on the thread they are pinned to
+                let mut world = from_main.recv().unwrap();
+                world.remove_non_send_resource::<u8>().unwrap();
+                to_main.send(world).unwrap();
+            });
+
+            to_worker.send(world).unwrap();
+            let mut world = from_worker.recv().unwrap();
+            let mut dispatch = Dispatch::new();
+            dispatch.add_system(Box::new(CountCalls));
+            dispatch.dispatch_systems(&mut world);
+            assert_eq!(world.get_non_send_resource::<Rc<Cell<u32>>>().unwrap().get(), 1);
+            to_worker.send(world).unwrap();
+            world = from_worker.recv().unwrap();
+
+            // but the system can't run away from the thread its resource is pinned to
+            let result = thread::scope(|s| s.spawn(||
+            {
+                let mut dispatch = Dispatch::new();
+                dispatch.add_system(Box::new(CountCalls));
+                dispatch.dispatch_systems(&mut world);
+            }).join());
+            assert!(result.is_err());
+            assert_eq!(world.get_non_send_resource::<Rc<Cell<u32>>>().unwrap().get(), 1);
+        });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_non_send_mut_param()
+    {
+        let mut world = World::new();
+        world.insert_non_send_resource(Vec::<u32>::new());
+        assert!(!PushCall.is_send());
+
+        let mut dispatch = Dispatch::new();
+        dispatch.add_system(Box::new(PushCall));
+        dispatch.dispatch_systems(&mut world);
+        dispatch.dispatch_systems(&mut world);
+        assert_eq!(*world.get_non_send_resource::<Vec<u32>>().unwrap(), vec![0, 1]);
+    }
+
+    struct CountCalls;
+
+    impl ParamSystem for CountCalls
+    {
+        type Param<'w> = (NonSend<'w, Rc<Cell<u32>>>, Option<Res<'w, Frames>>);
+
+        fn run_with(&mut self, (calls, _): Self::Param<'_>)
+        {
+            calls.set(calls.get() + 1);
+        }
+    }
+
+    struct PushCall;
+
+    impl ParamSystem for PushCall
+    {
+        type Param<'w> = NonSendMut<'w, Vec<u32>>;
+
+        fn run_with(&mut self, mut calls: Self::Param<'_>)
+        {
+            let count = calls.len() as u32;
+            calls.push(count);
+        }
+    }
+
+    struct AddValue;
+
+    impl ParamSystem for AddValue
+    {
+        type Param<'w> = (Res<'w, Settings>, ResMut<'w, Frames>);
+
+        fn run_with(&mut self, (settings, mut frames): Self::Param<'_>)
+        {
+            frames.count += settings.value;
+        }
+    }
+
+    struct RebuildCache;
+
+    impl System for RebuildCache
+    {
+        fn run(&mut self, world: &mut World)
+        {
+            world.get_resource_mut::<Settings>().unwrap().rebuilds += 1;
+        }
+    }
+
+    struct CountFrames;
+
+    impl System for CountFrames
+    {
+        fn run(&mut self, world: &mut World)
+        {
+            world.init_resource::<Frames>();
+            world.get_resource_mut::<Frames>().unwrap().count += 1;
+        }
+    }
+
+    struct Settings
+    {
+        pub rebuilds: u32,
+        pub value: u32
+    }
+
+    impl Resource for Settings {}
+
+    #[derive(Default)]
+    struct Frames
+    {
+        pub count: u32
+    }
+
+    impl Resource for Frames {}
+}
@@ -0,0 +1,396 @@
+use std::collections::HashSet;
+
+use crate::{component::Component, component_store::VecStore, registry::Registry, system::System, world::World};
+
+/// Rotation quaternion, 2D rotations are rotations about the z axis
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quat
+{
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32
+}
+
+impl Quat
+{
+    pub const IDENTITY: Self = Self { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    /// Rotation of angle radians about axis, which doesn't need to be normalized
+    pub fn from_axis_angle(axis: [f32; 3], angle: f32) -> Self
+    {
+        let len = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+        if len == 0.0
+        {
+            return Self::IDENTITY;
+        }
+
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        let s = sin / len;
+        Self { x: axis[0] * s, y: axis[1] * s, z: axis[2] * s, w: cos }
+    }
+
+    /// Rotation of angle radians about the z axis, a 2D rotation
+    pub fn from_rotation_z(angle: f32) -> Self
+    {
+        Self::from_axis_angle([0.0, 0.0, 1.0], angle)
+    }
+
+    /// Returns the rotation applying other first, then self
+    pub fn mul(&self, other: &Quat) -> Quat
+    {
+        Quat {
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z
+        }
+    }
+
+    /// Rotates a vector
+    pub fn rotate(&self, v: [f32; 3]) -> [f32; 3]
+    {
+        let m = Mat4::from_scale_rotation_translation([1.0; 3], *self, [0.0; 3]);
+        m.transform_vector(v)
+    }
+}
+
+impl Default for Quat
+{
+    fn default() -> Self
+    {
+        Self::IDENTITY
+    }
+}
+
+/// Column major 4x4 affine matrix
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat4
+{
+    pub cols: [[f32; 4]; 4]
+}
+
+impl Mat4
+{
+    pub const IDENTITY: Self = Self { cols: [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0]
+    ] };
+
+    /// Matrix scaling first, then rotating, then translating
+    pub fn from_scale_rotation_translation(scale: [f32; 3], rotation: Quat, translation: [f32; 3]) -> Self
+    {
+        let Quat { x, y, z, w } = rotation;
+        let (x2, y2, z2) = (x + x, y + y, z + z);
+        let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+        let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+        let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+
+        Self { cols: [
+            [(1.0 - (yy + zz)) * scale[0], (xy + wz) * scale[0], (xz - wy) * scale[0], 0.0],
+            [(xy - wz) * scale[1], (1.0 - (xx + zz)) * scale[1], (yz + wx) * scale[1], 0.0],
+            [(xz + wy) * scale[2], (yz - wx) * scale[2], (1.0 - (xx + yy)) * scale[2], 0.0],
+            [translation[0], translation[1], translation[2], 1.0]
+        ] }
+    }
+
+    /// Returns the matrix applying other first, then self
+    pub fn mul(&self, other: &Mat4) -> Mat4
+    {
+        let mut cols = [[0.0; 4]; 4];
+        for (col, other_col) in cols.iter_mut().zip(&other.cols)
+        {
+            for (row, value) in col.iter_mut().enumerate()
+            {
+                *value = (0..4).map(|k| self.cols[k][row] * other_col[k]).sum();
+            }
+        }
+        Mat4 { cols }
+    }
+
+    /// Transforms a point, applying the translation
+    pub fn transform_point(&self, p: [f32; 3]) -> [f32; 3]
+    {
+        let v = self.transform_vector(p);
+        [v[0] + self.cols[3][0], v[1] + self.cols[3][1], v[2] + self.cols[3][2]]
+    }
+
+    /// Transforms a direction, ignoring the translation
+    pub fn transform_vector(&self, v: [f32; 3]) -> [f32; 3]
+    {
+        let c = &self.cols;
+        [
+            c[0][0] * v[0] + c[1][0] * v[1] + c[2][0] * v[2],
+            c[0][1] * v[0] + c[1][1] * v[1] + c[2][1] * v[2],
+            c[0][2] * v[0] + c[1][2] * v[1] + c[2][2] * v[2]
+        ]
+    }
+
+    /// Returns the translation part of the matrix
+    pub fn translation(&self) -> [f32; 3]
+    {
+        [self.cols[3][0], self.cols[3][1], self.cols[3][2]]
+    }
+}
+
+impl Default for Mat4
+{
+    fn default() -> Self
+    {
+        Self::IDENTITY
+    }
+}
+
+/// Transform of an entity relative to its parent, or to the world for entities without a parent.
+/// 2D transforms use x and y with rotations about the z axis.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalTransform
+{
+    pub translation: [f32; 3],
+    pub rotation: Quat,
+    pub scale: [f32; 3]
+}
+
+impl LocalTransform
+{
+    pub const IDENTITY: Self = Self { translation: [0.0; 3], rotation: Quat::IDENTITY, scale: [1.0; 3] };
+
+    pub fn from_translation(translation: [f32; 3]) -> Self
+    {
+        Self { translation, ..Self::IDENTITY }
+    }
+
+    /// 2D transform at x, y
+    pub fn from_xy(x: f32, y: f32) -> Self
+    {
+        Self::from_translation([x, y, 0.0])
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self
+    {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Sets a 2D rotation of angle radians
+    pub fn with_angle(self, angle: f32) -> Self
+    {
+        self.with_rotation(Quat::from_rotation_z(angle))
+    }
+
+    pub fn with_scale(mut self, scale: [f32; 3]) -> Self
+    {
+        self.scale = scale;
+        self
+    }
+
+    /// Returns the matrix of the transform
+    pub fn matrix(&self) -> Mat4
+    {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for LocalTransform
+{
+    fn default() -> Self
+    {
+        Self::IDENTITY
+    }
+}
+
+impl Component for LocalTransform
+{
+    type Storage = VecStore<Self>;
+
+    /// The GlobalTransform was computed from this transform, so it goes with it
+    fn on_remove(registry: &mut Registry, id: usize)
+    {
+        registry.remove_bundle::<(GlobalTransform,)>(id);
+    }
+}
+
+/// Transform of an entity relative to the world, computed by TransformPropagation
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct GlobalTransform
+{
+    matrix: Mat4,
+    parent: Option<usize> // parent the matrix was computed relative to
+}
+
+impl GlobalTransform
+{
+    pub fn matrix(&self) -> &Mat4
+    {
+        &self.matrix
+    }
+
+    pub fn translation(&self) -> [f32; 3]
+    {
+        self.matrix.translation()
+    }
+
+    /// Transforms a point from the entity's local space to world space
+    pub fn transform_point(&self, p: [f32; 3]) -> [f32; 3]
+    {
+        self.matrix.transform_point(p)
+    }
+
+    /// Transforms a 2D point from the entity's local space to world space
+    pub fn transform_point_2d(&self, p: [f32; 2]) -> [f32; 2]
+    {
+        let [x, y, _] = self.matrix.transform_point([p[0], p[1], 0.0]);
+        [x, y]
+    }
+}
+
+impl Component for GlobalTransform
+{
+    type Storage = VecStore<Self>;
+}
+
+/// Computes the GlobalTransform of every entity with a LocalTransform, returns how many were recomputed.
+/// Only entities whose LocalTransform changed since the last change tick, whose parent changed,
+/// or whose ancestors were recomputed are updated.
+pub fn propagate_transforms(world: &mut World) -> usize
+{
+    if world.get_components::<GlobalTransform>().is_none()
+    {
+        world.register_component::<GlobalTransform>();
+    }
+
+    let mut dirty: HashSet<usize> = HashSet::new();
+    for id in world.hierarchy_order()
+    {
+        let Some(local) = world.get_components::<LocalTransform>().and_then(|locals| *locals.get(id).ok()?) else
+        {
+            continue;
+        };
+
+        // parents are visited first, so their GlobalTransform is already up to date
+        let parent = world.parent(id).filter(|&parent| world.get_components::<LocalTransform>()
+            .is_some_and(|locals| locals.get(parent).is_ok_and(|local| local.is_some())));
+        let globals = world.get_components::<GlobalTransform>();
+        let global = globals.and_then(|globals| *globals.get(id).ok()?);
+        let is_dirty = parent.is_some_and(|parent| dirty.contains(&parent))
+            || world.is_component_changed::<LocalTransform>(id)
+            || global.is_none_or(|global| global.parent != parent);
+        if !is_dirty
+        {
+            continue;
+        }
+
+        let parent_matrix = parent
+            .and_then(|parent| globals.and_then(|globals| globals.get(parent).ok()?.map(|global| global.matrix)))
+            .unwrap_or(Mat4::IDENTITY);
+        let has_global = global.is_some();
+        let global = GlobalTransform { matrix: parent_matrix.mul(&local.matrix()), parent };
+        if has_global
+        {
+            if let Some(Ok(mut slot)) = world.get_components_mut::<GlobalTransform>().map(|globals| globals.get_mut(id))
+            {
+                *slot = Some(global);
+            }
+        }
+        else
+        {
+            world.insert_bundle(id, (global,));
+        }
+        dirty.insert(id);
+    }
+    dirty.len()
+}
+
+/// System propagating LocalTransforms down the hierarchy into GlobalTransforms
+pub struct TransformPropagation;
+
+impl System for TransformPropagation
+{
+    fn run(&mut self, world: &mut World)
+    {
+        propagate_transforms(world);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn assert_near(a: [f32; 3], b: [f32; 3])
+    {
+        assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn test_propagate_2d()
+    {
+        let mut world = World::new();
+        let root = world.spawn((LocalTransform::from_xy(10.0, 0.0).with_angle(FRAC_PI_2),));
+        let child = world.spawn((LocalTransform::from_xy(1.0, 0.0).with_scale([2.0, 2.0, 1.0]),));
+        world.set_parent(child, root).unwrap();
+
+        assert_eq!(propagate_transforms(&mut world), 2);
+        let globals = world.get_components::<GlobalTransform>().unwrap();
+        let global = globals.get(child).unwrap().unwrap();
+        // the child is rotated a quarter turn around its parent
+        assert_near(global.translation(), [10.0, 1.0, 0.0]);
+        let [x, y] = global.transform_point_2d([1.0, 0.0]);
+        assert_near([x, y, 0.0], [10.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn test_only_dirty_subtrees()
+    {
+        let mut world = World::new();
+        let a = world.spawn((LocalTransform::from_translation([1.0, 0.0, 0.0]),));
+        let b = world.spawn((LocalTransform::from_translation([0.0, 1.0, 0.0]),));
+        let c = world.spawn((LocalTransform::from_translation([0.0, 0.0, 1.0]),));
+        let other = world.spawn((LocalTransform::IDENTITY,));
+        world.set_parent(b, a).unwrap();
+        world.set_parent(c, b).unwrap();
+
+        assert_eq!(propagate_transforms(&mut world), 4);
+        world.clear_trackers();
+        assert_eq!(propagate_transforms(&mut world), 0);
+
+        // changing b recomputes b and c only
+        world.get_components_mut::<LocalTransform>().unwrap().get_mut(b).unwrap().as_mut().unwrap().translation = [0.0, 2.0, 0.0];
+        assert_eq!(propagate_transforms(&mut world), 2);
+        let global = world.get_components::<GlobalTransform>().unwrap().get(c).unwrap().unwrap();
+        assert_near(global.translation(), [1.0, 2.0, 1.0]);
+
+        // reparenting is picked up without touching the LocalTransform
+        world.clear_trackers();
+        world.set_parent(c, other).unwrap();
+        assert_eq!(propagate_transforms(&mut world), 1);
+        let global = world.get_components::<GlobalTransform>().unwrap().get(c).unwrap().unwrap();
+        assert_near(global.translation(), [0.0, 0.0, 1.0]);
+
+        // mutable access that only reads doesn't mark the transform changed
+        world.clear_trackers();
+        let locals = world.get_components_mut::<LocalTransform>().unwrap();
+        assert_eq!(locals.get_mut(a).unwrap().unwrap().translation, [1.0, 0.0, 0.0]);
+        assert_eq!(propagate_transforms(&mut world), 0);
+    }
+
+    #[test]
+    fn test_remove_local_transform()
+    {
+        let mut world = World::new();
+        let a = world.spawn((LocalTransform::from_translation([1.0, 0.0, 0.0]),));
+        propagate_transforms(&mut world);
+        assert!(world.get_components::<GlobalTransform>().unwrap().get(a).unwrap().is_some());
+
+        // no stale GlobalTransform is left behind
+        world.remove_bundle::<(LocalTransform,)>(a);
+        assert!(world.get_components::<GlobalTransform>().unwrap().get(a).unwrap().is_none());
+        assert_eq!(propagate_transforms(&mut world), 0);
+    }
+}
@@ -0,0 +1,486 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{BTreeMap, HashMap}
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    bundle::Bundle,
+    component::Component,
+    component_store::TypedStore,
+    entity_map::EntityMap,
+    hierarchy::{Children, Parent},
+    name::Name,
+    registry::Registry,
+    resource::{Resource, Resources},
+    scene::{Scene, SceneEntity, SceneError},
+    entity::EntityManager,
+    snapshot::{ComponentColumn, Delta, Snapshot}
+};
+
+/// A component or resource value with its type erased
+type BoxedValue = Box<dyn Any + Send + Sync>;
+
+/// Binary encoding of a value, None if there was no value to encode
+type Encoded = Option<Result<Vec<u8>, bincode::Error>>;
+
+/// Functions saving and loading one registered component type
+struct ComponentRegistration
+{
+    name: String,
+    serialize: fn(&Registry, usize) -> Option<Result<Value, serde_json::Error>>,
+    deserialize: fn(Value) -> Result<BoxedValue, serde_json::Error>,
+    encode: fn(&Registry, usize) -> Encoded,
+    decode: fn(&[u8]) -> Result<BoxedValue, bincode::Error>,
+    register: fn(&mut Registry), // registers the component in a registry that doesn't have it yet, running on_register
+    insert: fn(&mut Registry, usize, BoxedValue),
+    remove: fn(&mut Registry, usize)
+}
+
+/// Functions saving and loading one registered resource type
+struct ResourceRegistration
+{
+    name: String,
+    serialize: fn(&Resources) -> Option<Result<Value, serde_json::Error>>,
+    deserialize: fn(Value) -> Result<BoxedValue, serde_json::Error>,
+    encode: fn(&Resources) -> Encoded,
+    decode: fn(&[u8]) -> Result<BoxedValue, bincode::Error>,
+    insert: fn(&mut Resources, BoxedValue),
+    remove: fn(&mut Resources)
+}
+
+/// Maps stable type names to component and resource types, so they can be saved and loaded.
+/// Parent, Children and Name are registered by default.
+pub struct TypeRegistry
+{
+    names: HashMap<String, TypeId>,
+    components: HashMap<TypeId, ComponentRegistration>,
+    resources: HashMap<TypeId, ResourceRegistration>
+}
+
+impl TypeRegistry
+{
+    pub fn new() -> Self
+    {
+        let mut types = Self { names: HashMap::new(), components: HashMap::new(), resources: HashMap::new() };
+        types.register_component::<Parent>("my_ecs::Parent");
+        types.register_component::<Children>("my_ecs::Children");
+        types.register_component::<Name>("my_ecs::Name");
+        #[cfg(feature = "transform")]
+        types.register_component::<crate::transform::LocalTransform>("my_ecs::LocalTransform");
+        types
+    }
+
+    /// Registers a component type under a name that stays the same across builds
+    pub fn register_component<T: Component + Serialize + DeserializeOwned>(&mut self, name: &str)
+    {
+        let type_id = TypeId::of::<T>();
+        self.names.insert(name.to_owned(), type_id);
+        self.components.insert(type_id, ComponentRegistration {
+            name: name.to_owned(),
+            serialize: | registry: &Registry, id: usize |
+            {
+                let component = registry.get_components::<T>()?.get(id).ok()?;
+                component.as_ref().map(serde_json::to_value)
+            },
+            deserialize: | value: Value | Ok(Box::new(serde_json::from_value::<T>(value)?)),
+            encode: | registry: &Registry, id: usize |
+            {
+                let component = registry.get_components::<T>()?.get(id).ok()?;
+                component.as_ref().map(bincode::serialize)
+            },
+            decode: | bytes: &[u8] | Ok(Box::new(bincode::deserialize::<T>(bytes)?)),
+            register: <(T,) as Bundle>::register_components,
+            insert: | registry: &mut Registry, id: usize, value: BoxedValue |
+            {
+                if let Ok(component) = value.downcast::<T>()
+                {
+                    registry.insert_bundle(id, (*component,));
+                }
+            },
+            remove: | registry: &mut Registry, id: usize | { registry.remove_bundle::<(T,)>(id); }
+        });
+    }
+
+    /// Registers a resource type under a name that stays the same across builds
+    pub fn register_resource<T: Resource + Serialize + DeserializeOwned>(&mut self, name: &str)
+    {
+        let type_id = TypeId::of::<T>();
+        self.names.insert(name.to_owned(), type_id);
+        self.resources.insert(type_id, ResourceRegistration {
+            name: name.to_owned(),
+            serialize: | resources: &Resources | resources.get::<T>().ok().map(|resource| serde_json::to_value(&*resource)),
+            deserialize: | value: Value | Ok(Box::new(serde_json::from_value::<T>(value)?)),
+            encode: | resources: &Resources | resources.get::<T>().ok().map(|resource| bincode::serialize(&*resource)),
+            decode: | bytes: &[u8] | Ok(Box::new(bincode::deserialize::<T>(bytes)?)),
+            insert: | resources: &mut Resources, value: BoxedValue |
+            {
+                if let Ok(resource) = value.downcast::<T>()
+                {
+                    resources.add(*resource);
+                }
+            },
+            remove: | resources: &mut Resources | { resources.remove::<T>(); }
+        });
+    }
+
+    /// Returns the type registered under name
+    pub fn type_id(&self, name: &str) -> Option<TypeId>
+    {
+        self.names.get(name).copied()
+    }
+
+    /// Returns the name a type was registered under
+    pub fn name_of(&self, type_id: TypeId) -> Option<&str>
+    {
+        self.components.get(&type_id).map(|c| c.name.as_str())
+            .or_else(|| self.resources.get(&type_id).map(|r| r.name.as_str()))
+    }
+
+    /// Saves every active entity with its registered components, and every registered resource
+    pub(crate) fn save_scene(&self, registry: &Registry, resources: &Resources) -> Result<Scene, SceneError>
+    {
+        let mut scene = Scene::default();
+        for entity in registry.entities.active.iter().filter(|e| e.is_active)
+        {
+            let mut components = BTreeMap::new();
+            for type_id in &entity.type_ids
+            {
+                let Some(registration) = self.components.get(type_id) else
+                {
+                    continue;
+                };
+                if let Some(value) = (registration.serialize)(registry, entity.id)
+                {
+                    let value = value.map_err(|err| SceneError { message: format!("Failed to save component {} of entity {}: {err}", registration.name, entity.label()) })?;
+                    components.insert(registration.name.clone(), value);
+                }
+            }
+            scene.entities.push(SceneEntity { id: entity.id, components });
+        }
+
+        for registration in self.resources.values()
+        {
+            if let Some(value) = (registration.serialize)(resources)
+            {
+                let value = value.map_err(|err| SceneError { message: format!("Failed to save resource {}: {err}", registration.name) })?;
+                scene.resources.insert(registration.name.clone(), value);
+            }
+        }
+        Ok(scene)
+    }
+
+    /// Spawns the entities of a scene and inserts its resources, returns the ids the entities were given.
+    /// Nothing is spawned if any value fails to load.
+    pub(crate) fn load_scene(&self, scene: &Scene, registry: &mut Registry, resources: &mut Resources) -> Result<EntityMap, SceneError>
+    {
+        // deserialize everything first so a bad scene leaves the World untouched
+        let mut entities = Vec::with_capacity(scene.entities.len());
+        for entity in &scene.entities
+        {
+            let mut components = Vec::with_capacity(entity.components.len());
+            for (name, value) in &entity.components
+            {
+                let (type_id, registration) = self.type_id(name).and_then(|type_id| Some((type_id, self.components.get(&type_id)?)))
+                    .ok_or_else(|| SceneError { message: format!("Failed to load entity {}: unknown component type {name}", entity.id) })?;
+                let component = (registration.deserialize)(value.clone())
+                    .map_err(|err| SceneError { message: format!("Failed to load component {name} of entity {}: {err}", entity.id) })?;
+                components.push((type_id, registration, component));
+            }
+            entities.push((entity.id, components));
+        }
+
+        let mut loaded_resources = Vec::with_capacity(scene.resources.len());
+        for (name, value) in &scene.resources
+        {
+            let (type_id, registration) = self.type_id(name).and_then(|type_id| Some((type_id, self.resources.get(&type_id)?)))
+                .ok_or_else(|| SceneError { message: format!("Failed to load resource: unknown resource type {name}") })?;
+            let resource = (registration.deserialize)(value.clone())
+                .map_err(|err| SceneError { message: format!("Failed to load resource {name}: {err}") })?;
+            loaded_resources.push((type_id, registration, resource));
+        }
+
+        // spawn every entity before inserting components, so references between them can be mapped
+        let mut map = EntityMap::new();
+        let ids = registry.reserve_entities(entities.len());
+        for ((old, _), &new) in entities.iter().zip(&ids)
+        {
+            map.insert(*old, new);
+        }
+
+        for ((_, components), id) in entities.into_iter().zip(ids)
+        {
+            for (type_id, registration, mut component) in components
+            {
+                // registering first lets the component add its map_entities, as Related does
+                (registration.register)(registry);
+                // values referring to entities outside the scene are dropped
+                if !registry.map_entities(type_id, &mut component, &map)
+                {
+                    continue;
+                }
+                (registration.insert)(registry, id, component);
+            }
+        }
+
+        for (type_id, registration, mut resource) in loaded_resources
+        {
+            if !registry.map_entities(type_id, &mut resource, &map)
+            {
+                continue;
+            }
+            (registration.insert)(resources, resource);
+        }
+        Ok(map)
+    }
+}
+
+impl TypeRegistry
+{
+    /// Copies the entity allocator, every registered component and every registered resource
+    pub(crate) fn snapshot(&self, registry: &Registry, resources: &Resources) -> Result<Snapshot, SceneError>
+    {
+        let mut snapshot = Snapshot {
+            active: registry.entities.active.iter().map(|e| e.is_active).collect(),
+            dropped: registry.entities.dropped.clone(),
+            ..Snapshot::default()
+        };
+
+        // sorted by name so equal worlds produce equal snapshots
+        let mut components: Vec<(&TypeId, &ComponentRegistration)> = self.components.iter().collect();
+        components.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        for (type_id, registration) in components
+        {
+            let mut values = Vec::new();
+            for entity in registry.entities.active.iter().filter(|e| e.is_active && e.type_ids.contains(type_id))
+            {
+                if let Some(bytes) = (registration.encode)(registry, entity.id)
+                {
+                    let bytes = bytes.map_err(|err| SceneError { message: format!("Failed to snapshot component {} of entity {}: {err}", registration.name, entity.label()) })?;
+                    values.push((entity.id, bytes));
+                }
+            }
+            if !values.is_empty()
+            {
+                snapshot.components.push(ComponentColumn { name: registration.name.clone(), values });
+            }
+        }
+
+        snapshot.resources = self.snapshot_resources(resources)?;
+        Ok(snapshot)
+    }
+
+    /// Replaces every entity with the ones in the snapshot, keeping their ids.
+    /// Components of types that aren't registered are dropped, registered resources missing from the snapshot are removed.
+    /// Nothing changes if any value fails to load.
+    pub(crate) fn restore(&self, snapshot: &Snapshot, registry: &mut Registry, resources: &mut Resources) -> Result<(), SceneError>
+    {
+        let mut components = Vec::new();
+        for column in &snapshot.components
+        {
+            let registration = self.type_id(&column.name).and_then(|type_id| self.components.get(&type_id))
+                .ok_or_else(|| SceneError { message: format!("Failed to restore snapshot: unknown component type {}", column.name) })?;
+            for (id, bytes) in &column.values
+            {
+                if !snapshot.active.get(*id).copied().unwrap_or(false)
+                {
+                    return Err(SceneError { message: format!("Failed to restore snapshot: component {} stored for inactive entity {id}", column.name) });
+                }
+                let component = (registration.decode)(bytes)
+                    .map_err(|err| SceneError { message: format!("Failed to restore component {} of entity {id}: {err}", column.name) })?;
+                components.push((registration, *id, component));
+            }
+        }
+
+        let mut loaded_resources = Vec::new();
+        for (name, bytes) in &snapshot.resources
+        {
+            let registration = self.type_id(name).and_then(|type_id| self.resources.get(&type_id))
+                .ok_or_else(|| SceneError { message: format!("Failed to restore snapshot: unknown resource type {name}") })?;
+            let resource = (registration.decode)(bytes)
+                .map_err(|err| SceneError { message: format!("Failed to restore resource {name}: {err}") })?;
+            loaded_resources.push((registration, resource));
+        }
+
+        // inserting runs the on_add hooks, which rebuild the name and relation indexes
+        registry.reset_entities(EntityManager::from_parts(&snapshot.active, snapshot.dropped.clone()));
+        for (registration, id, component) in components
+        {
+            (registration.insert)(registry, id, component);
+        }
+
+        self.resources.values().for_each(|registration| (registration.remove)(resources));
+        for (registration, resource) in loaded_resources
+        {
+            (registration.insert)(resources, resource);
+        }
+        Ok(())
+    }
+}
+
+impl TypeRegistry
+{
+    /// Returns the changes since base was taken, only encoding components that changed after tick
+    pub(crate) fn delta_since(&self, base: &Snapshot, tick: u64, registry: &Registry, resources: &Resources) -> Result<Delta, SceneError>
+    {
+        let active: Vec<bool> = registry.entities.active.iter().map(|e| e.is_active).collect();
+        let was_active = | id: usize | base.active.get(id).copied().unwrap_or(false);
+        let mut delta = Delta {
+            slots: active.len(),
+            dropped: registry.entities.dropped.clone(),
+            spawned: (0..active.len()).filter(|&id| active[id] && !was_active(id)).collect(),
+            despawned: (0..base.active.len()).filter(|&id| was_active(id) && !active.get(id).copied().unwrap_or(false)).collect(),
+            ..Delta::default()
+        };
+
+        let mut components: Vec<(&TypeId, &ComponentRegistration)> = self.components.iter().collect();
+        components.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        for (type_id, registration) in components
+        {
+            let base_values: HashMap<usize, &[u8]> = base.components.iter()
+                .find(|column| column.name == registration.name)
+                .map(|column| column.values.iter().map(|(id, bytes)| (*id, bytes.as_slice())).collect())
+                .unwrap_or_default();
+            let store = registry.components.get(type_id);
+
+            let mut inserted = ComponentColumn { name: registration.name.clone(), values: Vec::new() };
+            let mut changed = ComponentColumn { name: registration.name.clone(), values: Vec::new() };
+            for entity in registry.entities.active.iter().filter(|e| e.is_active && e.type_ids.contains(type_id))
+            {
+                let base_bytes = base_values.get(&entity.id).filter(|_| was_active(entity.id));
+                let is_changed = store.and_then(|store| store.ticks(entity.id)).is_none_or(|ticks| ticks.is_changed(tick));
+                if base_bytes.is_some() && !is_changed
+                {
+                    continue;
+                }
+
+                let Some(bytes) = (registration.encode)(registry, entity.id) else
+                {
+                    continue;
+                };
+                let bytes = bytes.map_err(|err| SceneError { message: format!("Failed to encode component {} of entity {}: {err}", registration.name, entity.label()) })?;
+                match base_bytes
+                {
+                    None => inserted.values.push((entity.id, bytes)),
+                    Some(old) if *old != bytes.as_slice() => changed.values.push((entity.id, bytes)),
+                    Some(_) => {}
+                }
+            }
+            delta.push_columns(inserted, changed);
+
+            let removed: Vec<usize> = base_values.keys().copied()
+                .filter(|&id| registry.entities.get(id).is_some_and(|e| e.is_active && !e.type_ids.contains(type_id)))
+                .collect();
+            if !removed.is_empty()
+            {
+                let mut removed = removed;
+                removed.sort();
+                delta.removed.push((registration.name.clone(), removed));
+            }
+        }
+
+        let current = self.snapshot_resources(resources)?;
+        delta.diff_resources(&base.resources, &current);
+        Ok(delta)
+    }
+
+    /// Applies a delta to a World in the state it was computed from, every entity keeps its id.
+    /// Nothing changes if any value fails to load.
+    pub(crate) fn apply_delta(&self, delta: &Delta, registry: &mut Registry, resources: &mut Resources) -> Result<(), SceneError>
+    {
+        let mut components = Vec::new();
+        for column in delta.inserted.iter().chain(&delta.changed)
+        {
+            let registration = self.component_registration(&column.name)?;
+            for (id, bytes) in &column.values
+            {
+                let component = (registration.decode)(bytes)
+                    .map_err(|err| SceneError { message: format!("Failed to apply component {} of entity {id}: {err}", column.name) })?;
+                components.push((registration, *id, component));
+            }
+        }
+
+        let mut removed = Vec::new();
+        for (name, ids) in &delta.removed
+        {
+            removed.push((self.component_registration(name)?, ids));
+        }
+
+        let mut loaded_resources = Vec::new();
+        for (name, bytes) in &delta.resources
+        {
+            let registration = self.resource_registration(name)?;
+            let resource = (registration.decode)(bytes)
+                .map_err(|err| SceneError { message: format!("Failed to apply resource {name}: {err}") })?;
+            loaded_resources.push((registration, resource));
+        }
+
+        let mut removed_resources = Vec::new();
+        for name in &delta.removed_resources
+        {
+            removed_resources.push(self.resource_registration(name)?);
+        }
+
+        for &id in &delta.despawned
+        {
+            registry.despawn(id);
+        }
+        registry.apply_allocator(delta.slots, delta.dropped.clone(), &delta.spawned);
+
+        for (registration, ids) in removed
+        {
+            ids.iter().for_each(|&id| (registration.remove)(registry, id));
+        }
+        for (registration, id, component) in components
+        {
+            (registration.insert)(registry, id, component);
+        }
+
+        removed_resources.into_iter().for_each(|registration| (registration.remove)(resources));
+        for (registration, resource) in loaded_resources
+        {
+            (registration.insert)(resources, resource);
+        }
+        Ok(())
+    }
+
+    fn component_registration(&self, name: &str) -> Result<&ComponentRegistration, SceneError>
+    {
+        self.type_id(name).and_then(|type_id| self.components.get(&type_id))
+            .ok_or_else(|| SceneError { message: format!("Unknown component type {name}") })
+    }
+
+    fn resource_registration(&self, name: &str) -> Result<&ResourceRegistration, SceneError>
+    {
+        self.type_id(name).and_then(|type_id| self.resources.get(&type_id))
+            .ok_or_else(|| SceneError { message: format!("Unknown resource type {name}") })
+    }
+
+    /// Encodes every registered resource, sorted by name
+    fn snapshot_resources(&self, resources: &Resources) -> Result<Vec<(String, Vec<u8>)>, SceneError>
+    {
+        let mut registrations: Vec<&ResourceRegistration> = self.resources.values().collect();
+        registrations.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut encoded = Vec::new();
+        for registration in registrations
+        {
+            if let Some(bytes) = (registration.encode)(resources)
+            {
+                let bytes = bytes.map_err(|err| SceneError { message: format!("Failed to snapshot resource {}: {err}", registration.name) })?;
+                encoded.push((registration.name.clone(), bytes));
+            }
+        }
+        Ok(encoded)
+    }
+}
+
+impl Default for TypeRegistry
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
@@ -1,18 +1,55 @@
 
-use std::any::Any;
-use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use std::any::{self, Any};
+use std::panic::{self, AssertUnwindSafe};
 
-use crate::component_store::VecStore;
+use crate::bundle::Bundle;
+use crate::command::{Command, CommandError, CommandQueue, Commands};
+use crate::component::{Component, ComponentId, ComponentInfo};
+use crate::dynamic::ComponentDescriptor;
 use crate::entity::Entity;
 use crate::entity_builder::EntityBuilder;
-use crate::query::QueryBuilder;
-use crate::resource::{ResourceError, Resources};
+use crate::hierarchy::HierarchyError;
+use crate::name::Name;
+use crate::prefab::Prefab;
+use crate::reflect::Reflect;
+use crate::query::{DynamicQuery, DynamicQueryItem, DynamicQueryItemMut, QueryBuilder, QueryData, QueryFilter};
+use crate::relation::{Related, Relation, RelationError};
+use crate::entity_map::{EntityMap, MapEntities};
+#[cfg(feature = "serde")]
+use crate::scene::{Scene, SceneError};
+#[cfg(feature = "serde")]
+use crate::snapshot::{Delta, Snapshot};
+#[cfg(feature = "serde")]
+use crate::type_registry::TypeRegistry;
+use crate::resource::{NonSend, NonSendMut, NonSendResources, Res, ResMut, Resource, ResourceError, Resources};
+use crate::system::SystemParam;
 use crate::registry::Registry;
 
+/// Creates a value from the contents of a World.
+/// Implemented for every type that implements Default.
+pub trait FromWorld
+{
+    fn from_world(world: &mut World) -> Self;
+}
+
+impl<T: Default> FromWorld for T
+{
+    fn from_world(_world: &mut World) -> Self
+    {
+        T::default()
+    }
+}
+
 pub struct World
 {
     resources: Resources,
-    registry: Registry
+    non_send_resources: NonSendResources,
+    registry: Registry,
+    command_queue: CommandQueue,
+    #[cfg(feature = "serde")]
+    type_registry: TypeRegistry,
+    change_tick: u64, // tick changes made now are marked with
+    last_change_tick: u64 // tick changes are detected relative to
 }
 
 
@@ -22,34 +59,340 @@ impl World
     {
         Self { 
             resources: Resources::new(),
-            registry: Registry::new()
+            non_send_resources: NonSendResources::new(),
+            registry: Registry::new(),
+            command_queue: CommandQueue::new(),
+            #[cfg(feature = "serde")]
+            type_registry: TypeRegistry::new(),
+            change_tick: 1,
+            last_change_tick: 0
         }
     }
 
     /// Adds a new component to the registry
-    pub fn register_component<T: Any + Send + Sync>(&mut self)
+    pub fn register_component<T: Component>(&mut self)
     {
         self.registry.register_component::<T>();
     }
 
+    /// Registers a component that is copied by clone_entity
+    pub fn register_cloneable<T: Component + Clone>(&mut self)
+    {
+        self.registry.register_cloneable::<T>();
+    }
+
+    /// Spawns a copy of an entity with a clone of every cloneable component, returns the new id.
+    /// Returns None if the entity isn't active.
+    pub fn clone_entity(&mut self, id: usize) -> Option<usize>
+    {
+        self.registry.clone_entity(id)
+    }
+
+    /// Spawns an entity with a clone of every component recorded in the prefab, returns the id
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) -> usize
+    {
+        self.registry.spawn_prefab_with(prefab, ())
+    }
+
+    /// Spawns an entity from a prefab, using the components in overrides instead of recorded ones of the same type
+    pub fn spawn_prefab_with<B: Bundle>(&mut self, prefab: &Prefab, overrides: B) -> usize
+    {
+        self.registry.spawn_prefab_with(prefab, overrides)
+    }
+
     /// Creates a new EntityBuilder instance
-    pub fn create_entity(&mut self) -> EntityBuilder
+    pub fn create_entity(&mut self) -> EntityBuilder<'_>
     {
         self.registry.create_entity()
     }
 
-    /// Revtrieves a Vecstore of Type T components if they exist
-    pub fn get_components<T: Any>(&self) -> Option<&VecStore<T>>
+    /// Spawns a new entity with every component in the bundle, returns the id
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> usize
+    {
+        self.registry.spawn(bundle)
+    }
+
+    /// Removes every component of an entity and deactivates it, returns false if it wasn't active
+    pub fn despawn(&mut self, id: usize) -> bool
+    {
+        self.registry.despawn(id)
+    }
+
+    /// Remaps the entity ids stored in components or resources of type T
+    /// when they are moved in from another World or loaded from a scene
+    pub fn register_map_entities<T: MapEntities + Send + Sync + 'static>(&mut self)
+    {
+        self.registry.register_map_entities::<T>();
+    }
+
+    /// Makes components of type T accessible through Reflect, registering the component itself if needed
+    pub fn register_reflect<T: Component + Reflect>(&mut self)
+    {
+        self.registry.register_reflect::<T>();
+    }
+
+    /// Registers a component type defined at runtime, returns the id to access it by
+    pub fn register_dynamic_component(&mut self, descriptor: ComponentDescriptor) -> ComponentId
+    {
+        self.registry.register_dynamic_component(descriptor)
+    }
+
+    /// Returns the id the component type T is registered under
+    pub fn component_id<T: Component>(&self) -> Option<ComponentId>
+    {
+        self.registry.component_id::<T>()
+    }
+
+    /// Returns the name and layout of a registered component type
+    pub fn component_info(&self, id: ComponentId) -> Option<&ComponentInfo>
+    {
+        self.registry.component_info(id)
+    }
+
+    /// Returns the registered component type with the given name, the full type name for Rust components
+    pub fn component_info_by_name(&self, name: &str) -> Option<&ComponentInfo>
+    {
+        self.registry.component_info_by_name(name)
+    }
+
+    /// Iterates over every registered component type, in the order they were registered
+    pub fn component_infos(&self) -> impl Iterator<Item = &ComponentInfo> + '_
+    {
+        self.registry.component_infos()
+    }
+
+    /// Returns a pointer to the component registered under component on an entity.
+    /// The pointer is valid until the component is next inserted or removed.
+    pub fn get_by_id(&self, id: usize, component: ComponentId) -> Option<*const u8>
+    {
+        self.registry.get_by_id(id, component)
+    }
+
+    /// Returns a mutable pointer to the component registered under component on an entity, marking it changed
+    pub fn get_by_id_mut(&mut self, id: usize, component: ComponentId) -> Option<*mut u8>
+    {
+        self.registry.get_by_id_mut(id, component)
+    }
+
+    /// Moves the component data points to onto an entity, replacing the one it had.
+    /// Returns false if the entity isn't active or component isn't registered, data is left untouched then.
+    ///
+    /// # Safety
+    /// data must point to a valid value of the component type, which must not be used or dropped afterwards
+    pub unsafe fn insert_by_id(&mut self, id: usize, component: ComponentId, data: *const u8) -> bool
+    {
+        unsafe { self.registry.insert_by_id(id, component, data) }
+    }
+
+    /// Drops the component registered under component from an entity, returns false if it had none
+    pub fn remove_by_id(&mut self, id: usize, component: ComponentId) -> bool
+    {
+        self.registry.remove_by_id(id, component)
+    }
+
+    /// Calls f with the component registered under component on an entity, returns None if the entity doesn't have one
+    /// or the type wasn't registered with register_reflect
+    pub fn reflect<R>(&self, id: usize, component: ComponentId, f: impl FnOnce(&dyn Reflect) -> R) -> Option<R>
+    {
+        self.registry.reflect(id, component, f)
+    }
+
+    /// Calls f with the component registered under component on an entity mutably, marking it changed
+    pub fn reflect_mut<R>(&mut self, id: usize, component: ComponentId, f: impl FnOnce(&mut dyn Reflect) -> R) -> Option<R>
+    {
+        self.registry.reflect_mut(id, component, f)
+    }
+
+    /// Moves entities and all of their descendants out of other into this World, returns the new id of every moved entity.
+    /// Entity ids stored in Parent, Children, Related and components registered with register_map_entities are remapped,
+    /// components referring to an entity that wasn't moved are dropped.
+    pub fn move_entities(&mut self, other: &mut World, ids: &[usize]) -> EntityMap
+    {
+        self.flush();
+        other.flush();
+        self.registry.move_entities(&mut other.registry, ids)
+    }
+
+    /// Moves every entity out of other into this World, returns the new id of every moved entity
+    pub fn merge_from(&mut self, other: &mut World) -> EntityMap
+    {
+        self.flush();
+        other.flush();
+        self.registry.merge_from(&mut other.registry)
+    }
+
+    /// Reserves an entity id through a shared reference, the entity exists once the World is flushed
+    pub fn reserve_entity(&self) -> usize
+    {
+        self.registry.reserve_entity()
+    }
+
+    /// Returns Commands to record changes through a shared reference
+    pub fn commands(&self) -> Commands<'_>
+    {
+        Commands::new(self)
+    }
+
+    /// Queues a command to be applied when the World is flushed
+    pub fn queue_command(&self, command: Command)
+    {
+        self.command_queue.push(command);
+    }
+
+    /// Records that a queued command failed, for commands added with Commands::add
+    pub fn report_command_error(&mut self, error: CommandError)
+    {
+        self.command_queue.report(error);
+    }
+
+    /// Removes and returns the failures of every command applied since the last call, in order
+    pub fn take_command_errors(&mut self) -> Vec<CommandError>
+    {
+        self.command_queue.take_errors()
+    }
+
+    /// Activates reserved entities and applies queued commands in order,
+    /// including commands queued while applying them
+    pub fn flush(&mut self)
+    {
+        loop
+        {
+            self.registry.flush_reserved_entities();
+            let commands = self.command_queue.take();
+            if commands.is_empty()
+            {
+                break;
+            }
+
+            for command in commands
+            {
+                command(self);
+            }
+        }
+    }
+
+    /// Activates count empty entities, growing every component store once, returns the ids
+    pub fn reserve_entities(&mut self, count: usize) -> Vec<usize>
+    {
+        self.registry.reserve_entities(count)
+    }
+
+    /// Spawns an entity for every bundle, allocating ids and storage once, returns the ids
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, bundles: I) -> Vec<usize>
+    {
+        self.registry.spawn_batch(bundles)
+    }
+
+    /// Adds every component in the bundle to an existing entity
+    pub fn insert_bundle<B: Bundle>(&mut self, id: usize, bundle: B)
+    {
+        self.registry.insert_bundle(id, bundle);
+    }
+
+    /// Removes every component in the bundle from an entity, returns the bundle if all were present
+    pub fn remove_bundle<B: Bundle>(&mut self, id: usize) -> Option<B>
+    {
+        self.registry.remove_bundle::<B>(id)
+    }
+
+    /// Makes parent the parent of child, detaching child from its previous parent.
+    /// Fails if either entity isn't active or if it would create a cycle.
+    pub fn set_parent(&mut self, child: usize, parent: usize) -> Result<(), HierarchyError>
+    {
+        self.registry.set_parent(child, parent)
+    }
+
+    /// Adds child to the end of parent's children, same as set_parent
+    pub fn add_child(&mut self, parent: usize, child: usize) -> Result<(), HierarchyError>
+    {
+        self.registry.set_parent(child, parent)
+    }
+
+    /// Detaches an entity from its parent, returns the old parent if it had one
+    pub fn remove_parent(&mut self, child: usize) -> Option<usize>
+    {
+        self.registry.remove_parent(child)
+    }
+
+    /// Returns the parent of an entity if it has one
+    pub fn parent(&self, id: usize) -> Option<usize>
+    {
+        self.registry.parent(id)
+    }
+
+    /// Returns the children of an entity in the order they were added
+    pub fn children(&self, id: usize) -> Vec<usize>
+    {
+        self.registry.children(id)
+    }
+
+    /// Returns every descendant of an entity, depth first with parents before their children
+    pub fn descendants(&self, id: usize) -> Vec<usize>
+    {
+        self.registry.descendants(id)
+    }
+
+    /// Returns every active entity ordered so parents always come before their children
+    pub fn hierarchy_order(&self) -> Vec<usize>
+    {
+        self.registry.hierarchy_order()
+    }
+
+    /// Despawns an entity and all of its descendants, returns false if it wasn't active
+    pub fn despawn_recursive(&mut self, id: usize) -> bool
+    {
+        self.registry.despawn_recursive(id)
+    }
+
+    /// Links source to target through the relation, replacing an existing relation of the same type.
+    /// Fails if either entity isn't active.
+    pub fn add_relation<R: Relation>(&mut self, source: usize, relation: R, target: usize) -> Result<(), RelationError>
+    {
+        self.registry.add_relation(source, relation, target)
+    }
+
+    /// Removes the relation of type R from source, returns the old target if it had one
+    pub fn remove_relation<R: Relation>(&mut self, source: usize) -> Option<usize>
+    {
+        self.registry.remove_bundle::<(Related<R>,)>(source).map(|(related,)| related.target())
+    }
+
+    /// Returns the target of the relation of type R on source if it has one
+    pub fn target_of<R: Relation>(&self, source: usize) -> Option<usize>
+    {
+        self.registry.target_of::<R>(source)
+    }
+
+    /// Returns every entity with a relation of type R pointing at target
+    pub fn sources_of<R: Relation>(&self, target: usize) -> Vec<usize>
+    {
+        self.registry.sources_of::<R>(target)
+    }
+
+    /// Revtrieves the store of Type T components if it exists
+    pub fn get_components<T: Component>(&self) -> Option<&T::Storage>
     {
         self.registry.get_components::<T>()
     }
 
-    /// Revtrieves a mutable Vecstore of Type T components if they exist
-    pub fn get_components_mut<T: Any>(&mut self) -> Option<&mut VecStore<T>>
+    /// Revtrieves the mutable store of Type T components if it exists
+    pub fn get_components_mut<T: Component>(&mut self) -> Option<&mut T::Storage>
     {
         self.registry.get_components_mut::<T>()
     }
 
+    /// Returns true if the component of type T was added to the entity since the last change tick
+    pub fn is_component_added<T: Component>(&self, id: usize) -> bool
+    {
+        self.registry.is_component_added::<T>(id)
+    }
+
+    /// Returns true if the component of type T was added or mutably accessed since the last change tick
+    pub fn is_component_changed<T: Component>(&self, id: usize) -> bool
+    {
+        self.registry.is_component_changed::<T>(id)
+    }
+
     /// Returns an entity given an id if it exists
     pub fn get_entity(&self, id: usize) -> Option<&Entity>
     {
@@ -62,30 +405,431 @@ impl World
         self.registry.get_entity_mut(id)
     }
 
+    /// Names an entity, replacing its old Name and keeping the name index up to date
+    pub fn set_name(&mut self, id: usize, name: impl Into<Name>)
+    {
+        self.registry.set_name(id, name);
+    }
+
+    /// Returns the first entity with the Name name
+    pub fn find_by_name(&self, name: &str) -> Option<usize>
+    {
+        self.registry.find_by_name(name)
+    }
+
+    /// Returns every entity with the Name name, in the order they were named
+    pub fn find_all_by_name(&self, name: &str) -> Vec<usize>
+    {
+        self.registry.find_all_by_name(name)
+    }
+
+    /// Returns the id of an entity followed by its name if it has one, for messages
+    pub fn label(&self, id: usize) -> String
+    {
+        self.registry.label(id)
+    }
+
     /// Starts QueryBuilder
-    pub fn query(&self) -> QueryBuilder
+    pub fn query(&self) -> QueryBuilder<'_>
     {
         self.registry.query()
     }
 
-    pub fn add_resource<T: Any>(&mut self, resource: T)
+    /// Returns the ids of every entity matching a DynamicQuery
+    pub fn get_dynamic(&self, query: &DynamicQuery) -> Vec<usize>
+    {
+        query.get(&self.registry)
+    }
+
+    /// Returns every entity matching a DynamicQuery with pointers to its components
+    pub fn fetch_dynamic(&self, query: &DynamicQuery) -> Vec<DynamicQueryItem>
+    {
+        query.fetch(&self.registry)
+    }
+
+    /// Returns every entity matching a DynamicQuery with mutable pointers to its components, marking them changed
+    pub fn fetch_dynamic_mut(&mut self, query: &DynamicQuery) -> Vec<DynamicQueryItemMut>
+    {
+        query.fetch_mut(&mut self.registry)
+    }
+
+    /// Calls f with the id and the item of every entity matching Q, such as `(&Position, Option<&mut Velocity>)`.
+    /// Mutably fetched components are marked changed once mutably dereferenced.
+    pub fn query_each<Q: QueryData>(&mut self, f: impl FnMut(usize, Q::Item<'_>))
+    {
+        self.registry.query_each::<Q>(f);
+    }
+
+    /// Like query_each, only for entities passing the filter F, such as `(With<Health>, Or<(With<Enemy>, With<Neutral>)>)`
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self, f: impl FnMut(usize, Q::Item<'_>))
+    {
+        self.registry.query_filtered::<Q, F>(f);
+    }
+
+    pub fn add_resource<T: Resource>(&mut self, resource: T)
     {
         self.resources.add(resource);
     }
 
-    pub fn get_resource<T: Send + Sync + 'static>(&self) -> Result<RwLockReadGuard<T>, ResourceError>
+    /// Adds a resource built with FromWorld (or Default), only if one doesn't exist already
+    pub fn init_resource<T: Resource + FromWorld>(&mut self)
+    {
+        if !self.resources.contains::<T>()
+        {
+            let resource = T::from_world(self);
+            self.resources.add(resource);
+        }
+    }
+
+    /// Retrieves a resource, change detection is relative to the last change tick
+    pub fn get_resource<T: Resource>(&self) -> Result<Res<'_, T>, ResourceError>
     {
         self.resources.get::<T>()
     }
 
-    pub fn get_resource_mut<T: Send + Sync + 'static>(&mut self) -> Result<RwLockWriteGuard<T>, ResourceError>
+    /// Retrieves a mutable resource, mutating it marks it as changed at the current change tick
+    pub fn get_resource_mut<T: Resource>(&mut self) -> Result<ResMut<'_, T>, ResourceError>
     {
         self.resources.get_mut::<T>()
     }
 
-    pub fn remove_resource<T: Any>(&mut self)
+    /// Retrieves a resource without blocking, fails if it is currently mutably borrowed
+    pub fn try_get_resource<T: Resource>(&self) -> Result<Res<'_, T>, ResourceError>
+    {
+        self.resources.try_get::<T>()
+    }
+
+    /// Retrieves a mutable resource through a shared reference without blocking, fails if it is currently borrowed
+    pub fn try_get_resource_mut<T: Resource>(&self) -> Result<ResMut<'_, T>, ResourceError>
+    {
+        self.resources.try_get_mut::<T>()
+    }
+
+    /// Fetches a SystemParam, such as a Res or a struct deriving SystemParam
+    pub fn get_param<'w, P: SystemParam<'w>>(&'w self) -> Result<P, ResourceError>
+    {
+        P::fetch(self)
+    }
+
+    /// Returns true if a resource of type T exists
+    pub fn contains_resource<T: Any>(&self) -> bool
+    {
+        self.resources.contains::<T>()
+    }
+
+    /// Removes a resource and returns ownership of it if it existed
+    pub fn remove_resource<T: Resource>(&mut self) -> Option<T>
+    {
+        self.resources.remove::<T>()
+    }
+
+    /// Temporarily removes a resource so it can be mutated alongside the World, returns what the closure returned.
+    /// The resource is reinserted once the closure returns, or unwinds from a panic.
+    /// If the closure inserted another T, that one is dropped in favour of the scoped resource.
+    /// Fails only if there is no resource of type T.
+    pub fn resource_scope<T: Resource, R>(&mut self, f: impl FnOnce(&mut World, &mut T) -> R) -> Result<R, ResourceError>
+    {
+        let Some((mut resource, added)) = self.resources.take::<T>() else
+        {
+            return Err(ResourceError { message: format!("Failed to get resource of type {} for resource scope", any::type_name::<T>()) });
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self, &mut resource)));
+        // replaces and drops any T inserted inside the scope
+        self.resources.restore(resource, added);
+
+        match result
+        {
+            Ok(value) => Ok(value),
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+
+    /// Adds a resource that is not Send or Sync, pinned to the current thread
+    pub fn insert_non_send_resource<T: 'static>(&mut self, resource: T)
+    {
+        self.non_send_resources.add(resource);
+    }
+
+    /// Adds a non-send resource built with FromWorld (or Default), only if one doesn't exist already
+    pub fn init_non_send_resource<T: FromWorld + 'static>(&mut self)
+    {
+        if !self.non_send_resources.contains::<T>()
+        {
+            let resource = T::from_world(self);
+            self.non_send_resources.add(resource);
+        }
+    }
+
+    /// Retrieves a non-send resource, fails if called from a thread other than the one that inserted it
+    pub fn get_non_send_resource<T: 'static>(&self) -> Result<NonSend<'_, T>, ResourceError>
+    {
+        self.non_send_resources.get::<T>()
+    }
+
+    /// Retrieves a mutable non-send resource, fails if called from a thread other than the one that inserted it
+    pub fn get_non_send_resource_mut<T: 'static>(&mut self) -> Result<NonSendMut<'_, T>, ResourceError>
+    {
+        self.non_send_resources.get_mut::<T>()
+    }
+
+    /// Retrieves a mutable non-send resource through a shared reference, fails if it is borrowed or pinned to another thread
+    pub fn try_get_non_send_resource_mut<T: 'static>(&self) -> Result<NonSendMut<'_, T>, ResourceError>
+    {
+        self.non_send_resources.try_get_mut::<T>()
+    }
+
+    /// Fails if the non-send resource of type T is pinned to a thread other than the current one
+    pub fn validate_non_send_thread<T: 'static>(&self) -> Result<(), ResourceError>
+    {
+        self.non_send_resources.validate_thread::<T>()
+    }
+
+    /// Fails if any non-send resource is pinned to a thread other than the current one
+    pub fn validate_non_send_threads(&self) -> Result<(), ResourceError>
+    {
+        self.non_send_resources.validate_thread_all()
+    }
+
+    /// Removes a non-send resource, fails if called from a thread other than the one that inserted it
+    pub fn remove_non_send_resource<T: 'static>(&mut self) -> Result<(), ResourceError>
+    {
+        self.non_send_resources.remove::<T>()
+    }
+
+    /// Returns the registry of types that can be saved and loaded
+    #[cfg(feature = "serde")]
+    pub fn type_registry(&self) -> &TypeRegistry
+    {
+        &self.type_registry
+    }
+
+    /// Returns the mutable registry of types that can be saved and loaded
+    #[cfg(feature = "serde")]
+    pub fn type_registry_mut(&mut self) -> &mut TypeRegistry
+    {
+        &mut self.type_registry
+    }
+
+    /// Saves every active entity with its registered components, and every registered resource
+    #[cfg(feature = "serde")]
+    pub fn save_scene(&self) -> Result<Scene, SceneError>
+    {
+        self.type_registry.save_scene(&self.registry, &self.resources)
+    }
+
+    /// Spawns the entities of a scene and inserts its resources, returns the ids the saved entities were given.
+    /// Entity ids stored in Parent, Children, Related and types registered with register_map_entities are remapped,
+    /// values referring to entities outside the scene are dropped.
+    #[cfg(feature = "serde")]
+    pub fn load_scene(&mut self, scene: &Scene) -> Result<EntityMap, SceneError>
     {
-        self.resources.remove::<T>();
+        self.type_registry.load_scene(scene, &mut self.registry, &mut self.resources)
     }
-    
-}
\ No newline at end of file
+
+    /// Copies every entity, registered component and registered resource into a binary snapshot
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> Result<Snapshot, SceneError>
+    {
+        self.type_registry.snapshot(&self.registry, &self.resources)
+    }
+
+    /// Returns the World to the state of a snapshot, every entity keeps the id it had when the snapshot was taken.
+    /// Snapshots don't hold change ticks, dynamic components or types that aren't registered:
+    /// those components are dropped and every restored component counts as added at the current change tick.
+    #[cfg(feature = "serde")]
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SceneError>
+    {
+        self.registry.flush_reserved_entities();
+        self.type_registry.restore(snapshot, &mut self.registry, &mut self.resources)
+    }
+
+    /// Returns the changes made since base was taken, only encoding components changed after tick.
+    /// Pass the tick returned by increment_change_tick right after base was taken, or 0 to compare every component.
+    #[cfg(feature = "serde")]
+    pub fn delta_since(&self, base: &Snapshot, tick: u64) -> Result<Delta, SceneError>
+    {
+        self.type_registry.delta_since(base, tick, &self.registry, &self.resources)
+    }
+
+    /// Applies a delta to a World in the state it was computed from, every entity keeps its id.
+    /// Only registered types are touched, inserted and changed components are marked at the current change tick.
+    #[cfg(feature = "serde")]
+    pub fn apply_delta(&mut self, delta: &Delta) -> Result<(), SceneError>
+    {
+        self.type_registry.apply_delta(delta, &mut self.registry, &mut self.resources)
+    }
+
+    /// Returns the tick changes made now are marked with
+    pub fn change_tick(&self) -> u64
+    {
+        self.change_tick
+    }
+
+    /// Returns the tick changes are currently detected relative to
+    pub fn last_change_tick(&self) -> u64
+    {
+        self.last_change_tick
+    }
+
+    /// Sets the tick changes are detected relative to, usually the last run of a system
+    pub fn set_last_change_tick(&mut self, tick: u64)
+    {
+        self.last_change_tick = tick;
+        self.resources.last_change_tick = tick;
+        self.registry.last_change_tick = tick;
+    }
+
+    /// Advances the change tick and returns the previous one
+    pub fn increment_change_tick(&mut self) -> u64
+    {
+        let tick = self.change_tick;
+        self.change_tick += 1;
+        self.resources.change_tick = self.change_tick;
+        self.registry.set_change_tick(self.change_tick);
+        tick
+    }
+
+    /// Marks every change made so far as seen
+    pub fn clear_trackers(&mut self)
+    {
+        let tick = self.increment_change_tick();
+        self.set_last_change_tick(tick);
+    }
+
+}
+impl Default for World
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_init_resource()
+    {
+        let mut world = World::new();
+        world.add_resource(Gravity{ value: 5.0 });
+        world.init_resource::<Gravity>();
+        world.init_resource::<Physics>();
+
+        // existing resources are not overwritten
+        assert_eq!(world.get_resource::<Gravity>().unwrap().value, 5.0);
+        // FromWorld can read other resources
+        assert_eq!(world.get_resource::<Physics>().unwrap().gravity, 5.0);
+    }
+
+    #[test]
+    fn test_resource_scope()
+    {
+        let mut world = World::new();
+        world.add_resource(Gravity{ value: 1.0 });
+        world.add_resource(Physics{ gravity: 0.0 });
+
+        let result = world.resource_scope(|world: &mut World, physics: &mut Physics|
+        {
+            assert!(!world.contains_resource::<Physics>());
+            physics.gravity = world.get_resource::<Gravity>().unwrap().value;
+            physics.gravity
+        });
+
+        assert_eq!(result.unwrap(), 1.0);
+        assert!(world.contains_resource::<Physics>());
+        assert_eq!(world.get_resource::<Physics>().unwrap().gravity, 1.0);
+
+        // missing resources fail instead of calling the closure
+        world.remove_resource::<Gravity>();
+        assert!(world.resource_scope(|_world: &mut World, _gravity: &mut Gravity| {}).is_err());
+        let err = world.get_resource::<Gravity>().err().unwrap();
+        assert_eq!(err.to_string(), "Failed to get resource of type my_ecs::world::tests::Gravity");
+    }
+
+    #[test]
+    fn test_resource_scope_insert_conflict()
+    {
+        let mut world = World::new();
+        world.add_resource(Physics{ gravity: 1.0 });
+
+        let result = world.resource_scope(|world: &mut World, physics: &mut Physics|
+        {
+            physics.gravity = 2.0;
+            world.add_resource(Physics{ gravity: 3.0 });
+            physics.gravity * 10.0
+        });
+
+        // the scoped resource wins over the one inserted inside the scope, the closure's result is kept
+        assert_eq!(result.unwrap(), 20.0);
+        assert_eq!(world.get_resource::<Physics>().unwrap().gravity, 2.0);
+    }
+
+    #[test]
+    fn test_resource_scope_change_detection()
+    {
+        let mut world = World::new();
+        world.add_resource(Gravity{ value: 1.0 });
+        world.clear_trackers();
+
+        world.resource_scope(|_world: &mut World, _gravity: &mut Gravity| {}).unwrap();
+
+        let gravity = world.get_resource::<Gravity>().unwrap();
+        assert!(gravity.is_changed());
+        assert!(!gravity.is_added());
+    }
+
+    #[test]
+    fn test_non_send_resource_threads()
+    {
+        let mut world = World::new();
+        world.insert_non_send_resource(std::rc::Rc::new(1u32));
+        assert!(world.validate_non_send_thread::<std::rc::Rc<u32>>().is_ok());
+        assert!(world.validate_non_send_threads().is_ok());
+
+        // the resource is pinned to the thread that inserted it, not checked against where the World was created
+        std::thread::scope(|s| s.spawn(||
+        {
+            assert!(world.get_non_send_resource::<std::rc::Rc<u32>>().is_err());
+            assert!(world.validate_non_send_thread::<std::rc::Rc<u32>>().is_err());
+            assert!(world.validate_non_send_threads().is_err());
+            // resources that don't exist are never pinned
+            assert!(world.validate_non_send_thread::<u8>().is_ok());
+        }).join().unwrap());
+        assert_eq!(**world.get_non_send_resource::<std::rc::Rc<u32>>().unwrap(), 1);
+    }
+
+    pub struct Gravity
+    {
+        pub value: f32
+    }
+
+    impl Resource for Gravity {}
+
+    impl Default for Gravity
+    {
+        fn default() -> Self
+        {
+            Self { value: 9.8 }
+        }
+    }
+
+    pub struct Physics
+    {
+        pub gravity: f32
+    }
+
+    impl Resource for Physics {}
+
+    impl FromWorld for Physics
+    {
+        fn from_world(world: &mut World) -> Self
+        {
+            let gravity = world.get_resource::<Gravity>().map(|g| g.value).unwrap_or_default();
+            Self { gravity }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "derive")]
+
+use std::any::TypeId;
+
+use my_ecs::{prelude::*, Registry};
+
+#[derive(Component)]
+struct Position
+{
+    x: f32,
+    y: f32
+}
+
+#[derive(Component)]
+#[component(storage = "sparse")]
+struct Stunned
+{
+    turns: u32
+}
+
+#[derive(Component)]
+struct Health(u32);
+
+#[derive(Component, Reflect)]
+struct Stats
+{
+    strength: u32,
+    label: String,
+    #[reflect(skip)]
+    _cache: Vec<u8>
+}
+
+#[derive(Component)]
+#[component(storage = "sparse", on_add = count_added, on_remove = count_removed)]
+struct Selected;
+
+#[derive(Component, Default)]
+struct SelectionCount(i32);
+
+fn count_added(registry: &mut Registry, _id: usize)
+{
+    registry.get_components_mut::<SelectionCount>().unwrap().get_mut(0).unwrap().as_mut().unwrap().0 += 1;
+}
+
+fn count_removed(registry: &mut Registry, _id: usize)
+{
+    registry.get_components_mut::<SelectionCount>().unwrap().get_mut(0).unwrap().as_mut().unwrap().0 -= 1;
+}
+
+#[derive(Bundle)]
+struct UnitBundle
+{
+    position: Position,
+    health: Health
+}
+
+#[derive(Bundle)]
+struct EnemyBundle
+{
+    #[bundle]
+    unit: UnitBundle,
+    stunned: Stunned
+}
+
+#[derive(Resource, Default)]
+struct Gravity
+{
+    value: f32
+}
+
+#[derive(Resource, Default)]
+struct Ticks
+{
+    count: u32
+}
+
+#[derive(SystemParam)]
+struct PhysicsParams<'w>
+{
+    gravity: Res<'w, Gravity>,
+    ticks: ResMut<'w, Ticks>,
+    missing: Option<Res<'w, Missing>>
+}
+
+#[derive(Resource)]
+struct Missing;
+
+#[test]
+fn derive_component_storage()
+{
+    fn storage_of<T: Component>() -> TypeId
+    {
+        TypeId::of::<T::Storage>()
+    }
+
+    assert_eq!(storage_of::<Position>(), TypeId::of::<VecStore<Position>>());
+    assert_eq!(storage_of::<Stunned>(), TypeId::of::<SparseStore<Stunned>>());
+}
+
+#[test]
+fn derive_component_hooks()
+{
+    let mut world = World::new();
+    let counter = world.spawn((SelectionCount::default(),));
+    let selected = world.spawn((Selected,));
+    world.insert_bundle(counter, (Selected,));
+
+    let count = |world: &World| world.get_components::<SelectionCount>().unwrap().get(counter).unwrap().as_ref().unwrap().0;
+    assert_eq!(count(&world), 2);
+
+    world.remove_bundle::<(Selected,)>(selected);
+    assert_eq!(count(&world), 1);
+}
+
+#[test]
+fn derive_bundle()
+{
+    let mut world = World::new();
+    let unit = world.spawn(UnitBundle{ position: Position{ x: 1.0, y: 2.0 }, health: Health(10) });
+    let enemy = world.spawn(EnemyBundle{ unit: UnitBundle{ position: Position{ x: 0.0, y: 0.0 }, health: Health(5) }, stunned: Stunned{ turns: 2 } });
+
+    assert_eq!(world.query().with_component::<Position>().with_component::<Health>().get(), vec![unit, enemy]);
+    assert_eq!(world.query().with_component::<Stunned>().get(), vec![enemy]);
+
+    // the unit isn't stunned, so none of its components are removed
+    assert!(world.remove_bundle::<EnemyBundle>(unit).is_none());
+    assert_eq!(world.query().with_component::<Position>().with_component::<Health>().get(), vec![unit, enemy]);
+
+    let removed = world.remove_bundle::<EnemyBundle>(enemy).unwrap();
+    assert_eq!((removed.unit.position.x, removed.unit.position.y, removed.unit.health.0, removed.stunned.turns), (0.0, 0.0, 5, 2));
+    assert!(world.get_entity(enemy).unwrap().type_ids.is_empty());
+}
+
+#[test]
+fn derive_system_param() -> Result<(), ResourceError>
+{
+    let mut world = World::new();
+    world.init_resource::<Gravity>();
+    world.init_resource::<Ticks>();
+    world.get_resource_mut::<Gravity>()?.value = 9.8;
+
+    {
+        let mut params = world.get_param::<PhysicsParams>()?;
+        params.ticks.count += 1;
+        assert_eq!(params.gravity.value, 9.8);
+        assert!(params.missing.is_none());
+
+        // the resource is already borrowed mutably by the params
+        assert!(world.get_param::<ResMut<Ticks>>().is_err());
+    }
+
+    assert_eq!(world.get_resource::<Ticks>()?.count, 1);
+    assert!(PhysicsParams::is_send());
+    assert!(!<(PhysicsParams, NonSend<std::rc::Rc<u32>>)>::is_send());
+    Ok(())
+}
+
+#[test]
+fn derive_reflect()
+{
+    let mut world = World::new();
+    world.register_reflect::<Stats>();
+    let id = world.spawn((Stats{ strength: 3, label: "ogre".to_owned(), _cache: Vec::new() }, Health(1)));
+
+    let info = world.component_info_by_name(std::any::type_name::<Stats>()).unwrap();
+    assert_eq!((info.size(), info.align()), (std::mem::size_of::<Stats>(), std::mem::align_of::<Stats>()));
+    assert!(info.is_reflected());
+    let stats = info.id();
+    let health = world.component_id::<Health>().unwrap();
+    assert!(!world.component_info(health).unwrap().is_reflected());
+    assert!(world.reflect(id, health, |_| ()).is_none());
+
+    let names = world.reflect(id, stats, |stats| stats.fields().map(|(name, _)| name).collect::<Vec<_>>()).unwrap();
+    assert_eq!(names, vec!["strength", "label"]);
+
+    world.reflect_mut(id, stats, |stats| stats.set_field("strength", 7u32)).unwrap().unwrap();
+    assert_eq!(world.reflect(id, stats, |stats| *stats.get_field::<u32>("strength").unwrap()), Some(7));
+}
@@ -0,0 +1,262 @@
+#![cfg(feature = "serde")]
+
+use my_ecs::{prelude::*, ComponentDescriptor, Delta, Scene, SceneError, Snapshot};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct Health(u32);
+
+impl Component for Health
+{
+    type Storage = VecStore<Self>;
+}
+
+/// Stores an entity id that has to be remapped on load
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct Follows(usize);
+
+impl Component for Follows
+{
+    type Storage = VecStore<Self>;
+}
+
+impl MapEntities for Follows
+{
+    fn map_entities(&mut self, map: &EntityMap) -> bool
+    {
+        map.map(self.0).map(|id| self.0 = id).is_some()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+struct Level(u32);
+
+impl Resource for Level {}
+
+fn register_types(world: &mut World)
+{
+    // map_entities can be registered before the type itself
+    world.register_map_entities::<Follows>();
+    let types = world.type_registry_mut();
+    types.register_component::<Health>("game::Health");
+    types.register_component::<Follows>("game::Follows");
+    types.register_resource::<Level>("game::Level");
+}
+
+#[test]
+fn save_and_load_scene() -> Result<(), SceneError>
+{
+    let mut world = World::new();
+    register_types(&mut world);
+    world.add_resource(Level(3));
+    let leader = world.spawn((Health(10), Name::new("leader")));
+    let follower = world.spawn((Health(5), Follows(leader)));
+    world.set_parent(follower, leader).unwrap();
+
+    let json = world.save_scene()?.to_json()?;
+
+    // load into a World that already has entities, so every id changes
+    let mut loaded = World::new();
+    register_types(&mut loaded);
+    loaded.spawn((Health(1),));
+    loaded.spawn((Health(2),));
+    let map = loaded.load_scene(&Scene::from_json(&json)?)?;
+
+    let (leader, follower) = (map.map(leader).unwrap(), map.map(follower).unwrap());
+    assert_eq!(loaded.find_by_name("leader"), Some(leader));
+    assert_eq!(loaded.parent(follower), Some(leader));
+    assert_eq!(loaded.children(leader), vec![follower]);
+    assert_eq!(*loaded.get_components::<Follows>().unwrap().get(follower).unwrap(), Some(Follows(leader)));
+    assert_eq!(*loaded.get_components::<Health>().unwrap().get(leader).unwrap(), Some(Health(10)));
+    assert_eq!(loaded.get_resource::<Level>().unwrap().0, 3);
+    Ok(())
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct Targets;
+
+impl Relation for Targets {}
+
+#[test]
+fn scene_round_trips_relations() -> Result<(), SceneError>
+{
+    let mut world = World::new();
+    world.type_registry_mut().register_component::<Related<Targets>>("game::Targets");
+    let hunter = world.spawn((Health(10),));
+    let prey = world.spawn((Health(5),));
+    world.add_relation(hunter, Targets, prey).unwrap();
+
+    let json = world.save_scene()?.to_json()?;
+
+    // the loading World has never seen Related<Targets>, and its ids are shifted
+    let mut loaded = World::new();
+    loaded.type_registry_mut().register_component::<Related<Targets>>("game::Targets");
+    register_types(&mut loaded);
+    loaded.spawn((Health(1),));
+    let map = loaded.load_scene(&Scene::from_json(&json)?)?;
+
+    let (hunter, prey) = (map.map(hunter).unwrap(), map.map(prey).unwrap());
+    assert_eq!(loaded.target_of::<Targets>(hunter), Some(prey));
+    assert_eq!(loaded.sources_of::<Targets>(prey), vec![hunter]);
+
+    // restoring a snapshot keeps ids and rebuilds the relation index
+    let snapshot = loaded.snapshot()?;
+    loaded.despawn(prey);
+    assert_eq!(loaded.target_of::<Targets>(hunter), None);
+    loaded.restore(&snapshot)?;
+    assert_eq!(loaded.target_of::<Targets>(hunter), Some(prey));
+    assert_eq!(loaded.sources_of::<Targets>(prey), vec![hunter]);
+    Ok(())
+}
+
+#[test]
+fn unknown_types_fail_without_spawning()
+{
+    let mut world = World::new();
+    register_types(&mut world);
+    world.spawn((Health(10),));
+    let scene = world.save_scene().unwrap();
+
+    // a World that doesn't know game::Health can't load the scene
+    let mut other = World::new();
+    let err = other.load_scene(&scene).unwrap_err();
+    assert!(err.to_string().contains("game::Health"), "{err}");
+    assert!(other.get_entity(0).is_none());
+}
+
+#[test]
+fn snapshot_round_trips_exactly() -> Result<(), SceneError>
+{
+    let mut world = World::new();
+    register_types(&mut world);
+    world.add_resource(Level(1));
+    let a = world.spawn((Health(10), Name::new("a")));
+    let b = world.spawn((Health(20),));
+    let c = world.spawn((Health(30), Follows(a)));
+    world.set_parent(c, a).unwrap();
+    world.despawn(b);
+
+    let snapshot = Snapshot::from_bytes(&world.snapshot()?.to_bytes()?)?;
+
+    // diverge from the snapshot, then roll back
+    world.get_resource_mut::<Level>().unwrap().0 = 2;
+    world.despawn_recursive(a);
+    world.spawn((Health(99), Name::new("late")));
+    world.restore(&snapshot)?;
+
+    assert_eq!(world.snapshot()?, snapshot);
+    assert_eq!(world.find_by_name("a"), Some(a));
+    assert_eq!(world.find_by_name("late"), None);
+    assert_eq!(world.children(a), vec![c]);
+    assert_eq!(world.get_resource::<Level>().unwrap().0, 1);
+    // the dropped id is reused next, just like before the snapshot
+    assert_eq!(world.spawn((Health(0),)), b);
+    Ok(())
+}
+
+#[test]
+fn hierarchy_survives_restore_and_load() -> Result<(), SceneError>
+{
+    let mut world = World::new();
+    register_types(&mut world);
+    let root = world.spawn((Health(1),));
+    let children: Vec<usize> = (0..3).map(|i| world.spawn((Health(i),))).collect();
+    for &child in children.iter().rev()
+    {
+        world.set_parent(child, root).unwrap();
+    }
+    let order: Vec<usize> = children.iter().rev().copied().collect();
+
+    let snapshot = world.snapshot()?;
+    world.despawn_recursive(root);
+    world.restore(&snapshot)?;
+    assert_eq!(world.children(root), order);
+    assert!(order.iter().all(|&child| world.parent(child) == Some(root)));
+
+    let mut other = World::new();
+    register_types(&mut other);
+    let map = other.load_scene(&world.save_scene()?)?;
+    let root = map.map(root).unwrap();
+    let order: Vec<usize> = order.iter().map(|&child| map.map(child).unwrap()).collect();
+    assert_eq!(other.children(root), order);
+    assert!(order.iter().all(|&child| other.parent(child) == Some(root)));
+    Ok(())
+}
+
+#[test]
+fn deltas_bring_worlds_in_sync() -> Result<(), SceneError>
+{
+    let mut server = World::new();
+    register_types(&mut server);
+    server.add_resource(Level(1));
+    let a = server.spawn((Health(10), Name::new("a")));
+    let b = server.spawn((Health(20), Follows(a)));
+    let c = server.spawn((Health(30),));
+
+    let base = server.snapshot()?;
+    let tick = server.increment_change_tick();
+    let mut client = World::new();
+    register_types(&mut client);
+    client.restore(&base)?;
+
+    server.get_components_mut::<Health>().unwrap().get_mut(a).unwrap().as_mut().unwrap().0 = 11;
+    server.remove_bundle::<(Follows,)>(b);
+    let d = server.spawn((Health(40), Follows(b)));
+    server.despawn(c);
+    server.get_resource_mut::<Level>().unwrap().0 = 2;
+
+    let delta = server.delta_since(&base, tick)?;
+    assert_eq!(delta, base.delta(&server.snapshot()?));
+    assert_eq!(delta.spawned(), &[d]);
+    assert_eq!(delta.despawned(), &[c]);
+    assert_eq!(delta.changed("game::Health"), vec![a]);
+    assert_eq!(delta.inserted("game::Follows"), vec![d]);
+    assert_eq!(delta.removed("game::Follows"), vec![b]);
+
+    client.apply_delta(&Delta::from_bytes(&delta.to_bytes()?)?)?;
+    assert_eq!(client.snapshot()?, server.snapshot()?);
+    assert_eq!(client.get_resource::<Level>().unwrap().0, 2);
+    assert!(server.delta_since(&server.snapshot()?, server.change_tick())?.is_empty());
+    Ok(())
+}
+
+/// Never registered with the TypeRegistry, so snapshots and deltas can't carry it
+struct Unsaved;
+
+impl Component for Unsaved
+{
+    type Storage = VecStore<Self>;
+}
+
+#[test]
+fn restore_keeps_only_registered_components() -> Result<(), SceneError>
+{
+    let mut world = World::new();
+    register_types(&mut world);
+    let marker = world.register_dynamic_component(unsafe { ComponentDescriptor::new("script::Marker", std::alloc::Layout::new::<u8>(), None) });
+    let a = world.spawn((Health(10), Unsaved));
+    unsafe { world.insert_by_id(a, marker, &7u8) };
+    let snapshot = world.snapshot()?;
+
+    // ticks aren't part of the snapshot, restored components count as added when restored
+    world.clear_trackers();
+    assert!(!world.is_component_added::<Health>(a));
+    world.restore(&snapshot)?;
+    assert!(world.is_component_added::<Health>(a));
+    assert!(world.query().with_component::<Unsaved>().get().is_empty());
+    assert!(world.get_by_id(a, marker).is_none());
+
+    // a delta only touches registered types, changed components count as changed when applied
+    let mut client = World::new();
+    register_types(&mut client);
+    client.restore(&snapshot)?;
+    let b = client.spawn((Unsaved,));
+    let tick = world.increment_change_tick();
+    world.get_components_mut::<Health>().unwrap().get_mut(a).unwrap().as_mut().unwrap().0 = 11;
+    let delta = world.delta_since(&snapshot, tick)?;
+    client.clear_trackers();
+    client.apply_delta(&delta)?;
+    assert!(client.is_component_changed::<Health>(a) && !client.is_component_added::<Health>(a));
+    assert_eq!(client.query().with_component::<Unsaved>().get(), vec![b]);
+    Ok(())
+}
@@ -0,0 +1,366 @@
+use std::{alloc::Layout, any::TypeId};
+
+use my_ecs::{prelude::*, ComponentDescriptor, DynamicQuery, Registry};
+
+#[test]
+fn create_and_query_entities()
+{
+    let mut world = World::new();
+    world.register_component::<Health>();
+
+    let e1 = world.create_entity()
+        .with_component(Health{ value: 100 })
+        .with_component(Speed{ value: 10 })
+        .build();
+    let e2 = world.create_entity()
+        .with_component(Health{ value: 50 })
+        .build();
+
+    let with_health = world.query().with_component::<Health>().get();
+    assert_eq!(with_health, vec![e1, e2]);
+
+    let with_speed = world.query().with_component::<Health>().with_component::<Speed>().get();
+    assert_eq!(with_speed, vec![e1]);
+
+    let healths = world.get_components::<Health>().unwrap();
+    assert_eq!(healths.get(e2).unwrap().as_ref().unwrap().value, 50);
+}
+
+#[test]
+fn dispatch_systems_with_resources()
+{
+    let mut world = World::new();
+    world.add_resource(DeltaTime{ value: 0.5 });
+    world.create_entity().with_component(Speed{ value: 10 }).build();
+
+    let mut dispatch = Dispatch::new();
+    dispatch.add_system(Box::new(Accelerate));
+    dispatch.dispatch_systems(&mut world);
+
+    let speeds = world.get_components::<Speed>().unwrap();
+    assert_eq!(speeds.get(0).unwrap().as_ref().unwrap().value, 15);
+}
+
+#[test]
+fn resources_through_public_api() -> Result<(), ResourceError>
+{
+    let mut world = World::new();
+    world.init_resource::<Score>();
+    world.get_resource_mut::<Score>()?.points += 3;
+
+    let score: Res<Score> = world.get_resource::<Score>()?;
+    assert_eq!(score.points, 3);
+    assert!(score.is_added());
+    drop(score);
+
+    assert_eq!(world.remove_resource::<Score>().map(|s| s.points), Some(3));
+    assert!(!world.contains_resource::<Score>());
+    Ok(())
+}
+
+#[test]
+fn spawn_struct_bundle()
+{
+    let mut world = World::new();
+    let player = world.spawn(PlayerBundle{ health: Health{ value: 100 }, speed: Speed{ value: 3 } });
+
+    assert_eq!(world.query().with_component::<Health>().with_component::<Speed>().get(), vec![player]);
+
+    let removed = world.remove_bundle::<PlayerBundle>(player).unwrap();
+    assert_eq!((removed.health.value, removed.speed.value), (100, 3));
+    assert!(world.get_entity(player).unwrap().type_ids.is_empty());
+}
+
+#[test]
+fn merge_worlds_remaps_entities()
+{
+    struct Follows(usize);
+
+    impl Component for Follows
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    impl MapEntities for Follows
+    {
+        fn map_entities(&mut self, map: &EntityMap) -> bool
+        {
+            map.map(self.0).map(|id| self.0 = id).is_some()
+        }
+    }
+
+    let mut world = World::new();
+    world.spawn((Health{ value: 1 },));
+
+    let mut other = World::new();
+    other.register_map_entities::<Follows>();
+    let leader = other.spawn((Health{ value: 10 }, Name::new("leader")));
+    let follower = other.spawn((Speed{ value: 2 }, Follows(leader)));
+    let child = other.spawn((Health{ value: 5 },));
+    other.set_parent(child, follower).unwrap();
+
+    let map = world.merge_from(&mut other);
+    assert_eq!(map.len(), 3);
+    assert!(other.query().with_component::<Health>().get().is_empty());
+    assert_eq!(other.find_by_name("leader"), None);
+
+    let (leader, follower, child) = (map.map(leader).unwrap(), map.map(follower).unwrap(), map.map(child).unwrap());
+    assert_eq!(world.find_by_name("leader"), Some(leader));
+    assert_eq!(world.get_components::<Follows>().unwrap().get(follower).unwrap().as_ref().unwrap().0, leader);
+    assert_eq!(world.parent(child), Some(follower));
+    assert_eq!(world.children(follower), vec![child]);
+    assert_eq!(world.query().with_component::<Health>().get(), vec![0, leader, child]);
+
+    // moving a child takes it out of its parent's Children in the source world
+    let mut third = World::new();
+    let map = third.move_entities(&mut world, &[child]);
+    let moved = map.map(child).unwrap();
+    assert!(world.children(follower).is_empty());
+    assert_eq!(third.parent(moved), None);
+    assert_eq!(third.get_components::<Health>().unwrap().get(moved).unwrap().as_ref().unwrap().value, 5);
+}
+
+#[test]
+fn move_entities_drops_unmapped_references()
+{
+    struct Follows(usize);
+
+    impl Component for Follows
+    {
+        type Storage = VecStore<Self>;
+    }
+
+    impl MapEntities for Follows
+    {
+        fn map_entities(&mut self, map: &EntityMap) -> bool
+        {
+            map.map(self.0).map(|id| self.0 = id).is_some()
+        }
+    }
+
+    struct Targets;
+
+    impl Relation for Targets {}
+
+    let mut other = World::new();
+    other.register_map_entities::<Follows>();
+    let marker = other.register_dynamic_component(unsafe { ComponentDescriptor::new("script::Marker", Layout::new::<u8>(), None) });
+    let leader = other.spawn((Health{ value: 10 },));
+    let follower = other.spawn((Speed{ value: 2 }, Follows(leader)));
+    other.add_relation(follower, Targets, leader).unwrap();
+    unsafe { other.insert_by_id(follower, marker, &7u8) };
+
+    // only the follower moves, its references to the leader can't be kept
+    let mut world = World::new();
+    world.spawn((Health{ value: 1 },));
+    let map = world.move_entities(&mut other, &[follower]);
+    let moved = map.map(follower).unwrap();
+    assert_eq!(map.map(leader), None);
+    assert!(world.get_components::<Follows>().is_none_or(|follows| follows.get(moved).unwrap().is_none()));
+    assert_eq!(world.target_of::<Targets>(moved), None);
+    assert!(world.sources_of::<Targets>(leader).is_empty());
+    assert!(other.sources_of::<Targets>(leader).is_empty());
+    assert_eq!(world.get_components::<Speed>().unwrap().get(moved).unwrap().as_ref().unwrap().value, 2);
+
+    // runtime defined components move to the one registered under the same name
+    let moved_marker = world.component_info_by_name("script::Marker").unwrap().id();
+    assert_eq!(world.get_by_id(moved, moved_marker).map(|marker| unsafe { *marker }), Some(7));
+    assert_eq!(other.get_by_id(follower, marker), None);
+}
+
+#[test]
+fn dynamic_queries()
+{
+    let mut world = World::new();
+    let marker = world.register_dynamic_component(unsafe { ComponentDescriptor::new("script::Marker", Layout::new::<u8>(), None) });
+    let e1 = world.spawn((Health{ value: 1 }, Speed{ value: 2 }));
+    let e2 = world.spawn((Health{ value: 3 },));
+    let e3 = world.spawn((Speed{ value: 4 },));
+    unsafe { world.insert_by_id(e2, marker, &7u8) };
+
+    let health = world.component_id::<Health>().unwrap();
+    let speed = world.component_id::<Speed>().unwrap();
+
+    let items = world.fetch_dynamic(DynamicQuery::new().with(health).optional(speed).optional(marker));
+    assert_eq!(items.iter().map(|item| item.entity).collect::<Vec<_>>(), vec![e1, e2]);
+    let values: Vec<(u32, Option<u32>, Option<u8>)> = items.iter().map(|item| unsafe {(
+        (*(item.components[0] as *const Health)).value,
+        item.optional[0].map(|speed| (*(speed as *const Speed)).value),
+        item.optional[1].map(|marker| *marker)
+    )}).collect();
+    assert_eq!(values, vec![(1, Some(2), None), (3, None, Some(7))]);
+
+    assert_eq!(world.get_dynamic(DynamicQuery::new().with(marker)), vec![e2]);
+    assert_eq!(world.get_dynamic(DynamicQuery::new().with(speed).without(health)), vec![e3]);
+    assert_eq!(world.get_dynamic(DynamicQuery::new().with_type_id(TypeId::of::<Speed>()).without_type_id(TypeId::of::<Health>())), vec![e3]);
+    assert!(world.get_dynamic(DynamicQuery::new().with_type_id(TypeId::of::<DeltaTime>())).is_empty());
+
+    // a query can be kept and run again, mutable fetches mark the fetched components changed
+    let mut boosted = DynamicQuery::new();
+    boosted.with_type_id(TypeId::of::<Speed>()).optional(marker);
+    world.clear_trackers();
+    for item in world.fetch_dynamic_mut(&boosted)
+    {
+        unsafe
+        {
+            (*(item.components[0] as *mut Speed)).value *= 10;
+            assert!(item.optional[0].is_none());
+        }
+    }
+    assert_eq!(world.get_components::<Speed>().unwrap().get(e3).unwrap().as_ref().unwrap().value, 40);
+    assert_eq!(world.query().changed::<Speed>().get(), vec![e1, e3]);
+    assert!(!world.is_component_changed::<Health>(e1));
+}
+
+#[test]
+fn query_optional_components()
+{
+    let mut world = World::new();
+    let e1 = world.spawn((Health{ value: 10 }, Speed{ value: 2 }));
+    let e2 = world.spawn((Health{ value: 20 },));
+    world.spawn((Speed{ value: 5 },));
+
+    let mut seen = Vec::new();
+    world.query_each::<(&Health, Option<&Speed>)>(|id, (health, speed)| seen.push((id, health.value, speed.map(|s| s.value))));
+    assert_eq!(seen, vec![(e1, 10, Some(2)), (e2, 20, None)]);
+
+    world.clear_trackers();
+    world.query_each::<(&mut Health, Option<&mut Speed>)>(|_, (mut health, speed)|
+    {
+        health.value += speed.map_or(0, |mut s| { s.value *= 2; s.value });
+    });
+    assert_eq!(world.get_components::<Health>().unwrap().get(e1).unwrap().as_ref().unwrap().value, 14);
+    assert_eq!(world.get_components::<Speed>().unwrap().get(e1).unwrap().as_ref().unwrap().value, 4);
+    assert!(world.is_component_changed::<Health>(e2));
+    assert_eq!(world.query().changed::<Speed>().get(), vec![e1]);
+
+    // only reading through &mut T doesn't mark the component changed
+    world.clear_trackers();
+    let mut total = 0;
+    world.query_each::<&mut Health>(|_, health| total += health.value);
+    assert_eq!(total, 34);
+    assert!(!world.is_component_changed::<Health>(e1));
+    assert!(world.query().changed::<Health>().get().is_empty());
+}
+
+#[test]
+fn composite_query_filters()
+{
+    struct Enemy;
+    struct Neutral;
+    struct Boss;
+
+    impl Component for Enemy
+    {
+        type Storage = SparseStore<Self>;
+    }
+
+    impl Component for Neutral
+    {
+        type Storage = SparseStore<Self>;
+    }
+
+    impl Component for Boss
+    {
+        type Storage = SparseStore<Self>;
+    }
+
+    let mut world = World::new();
+    let enemy = world.spawn((Health{ value: 1 }, Enemy));
+    let neutral = world.spawn((Health{ value: 2 }, Neutral, Speed{ value: 3 }));
+    let boss = world.spawn((Health{ value: 3 }, Enemy, Boss));
+    let grunt = world.spawn((Enemy,));
+    world.spawn((Health{ value: 4 },));
+
+    assert_eq!(world.query().with_component::<Health>().filter::<Or<(With<Enemy>, With<Neutral>)>>().get(), vec![enemy, neutral, boss]);
+    // nested: neutrals, or enemies that aren't bosses
+    assert_eq!(world.query().filter::<Or<(With<Neutral>, (With<Enemy>, Without<Boss>))>>().get(), vec![enemy, neutral, grunt]);
+
+    let mut seen = Vec::new();
+    world.query_filtered::<&Health, Or<(With<Enemy>, With<Neutral>)>>(|id, health| seen.push((id, health.value)));
+    assert_eq!(seen, vec![(enemy, 1), (neutral, 2), (boss, 3)]);
+
+    let mut seen = Vec::new();
+    world.query_filtered::<AnyOf<(&Speed, &mut Boss)>, With<Health>>(|id, (speed, boss)| seen.push((id, speed.map(|s| s.value), boss.is_some())));
+    assert_eq!(seen, vec![(neutral, Some(3), false), (boss, None, true)]);
+}
+
+struct PlayerBundle
+{
+    health: Health,
+    speed: Speed
+}
+
+impl Bundle for PlayerBundle
+{
+    fn type_ids() -> Vec<TypeId>
+    {
+        <(Health, Speed)>::type_ids()
+    }
+
+    fn register_components(registry: &mut Registry)
+    {
+        <(Health, Speed)>::register_components(registry);
+    }
+
+    fn insert_components(self, registry: &mut Registry, id: usize)
+    {
+        (self.health, self.speed).insert_components(registry, id);
+    }
+
+    fn take_components(registry: &mut Registry, id: usize) -> Option<Self>
+    {
+        <(Health, Speed)>::take_components(registry, id).map(|(health, speed)| Self { health, speed })
+    }
+}
+
+struct Accelerate;
+
+impl System for Accelerate
+{
+    fn run(&mut self, world: &mut World)
+    {
+        let boost = (world.get_resource::<DeltaTime>().unwrap().value * 10.0) as u32;
+        let ids = world.query().with_component::<Speed>().get();
+        let speeds = world.get_components_mut::<Speed>().unwrap();
+        for id in ids
+        {
+            speeds.get_mut(id).unwrap().as_mut().unwrap().value += boost;
+        }
+    }
+}
+
+struct Health
+{
+    value: u32
+}
+
+impl Component for Health
+{
+    type Storage = VecStore<Self>;
+}
+
+struct Speed
+{
+    value: u32
+}
+
+impl Component for Speed
+{
+    type Storage = VecStore<Self>;
+}
+
+struct DeltaTime
+{
+    value: f32
+}
+
+impl Resource for DeltaTime {}
+
+#[derive(Default)]
+struct Score
+{
+    points: u32
+}
+
+impl Resource for Score {}